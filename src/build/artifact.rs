@@ -0,0 +1,137 @@
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use sha2::Digest;
+use sha2::Sha256;
+
+/// A file whose contents didn't match the checksum given for it in the config.
+#[derive(Debug)]
+pub struct ChecksumError {
+    pub location: String,
+    pub expected: String,
+    pub actual: String
+}
+
+impl fmt::Display for ChecksumError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} has sha256 {}, expected {}", self.location, self.actual, self.expected)
+    }
+}
+
+/// Turns the location of a `Static` or `Prebuilt` source into a path. Locations are either paths, relative to the
+/// working directory, or `file://` URLs. Any other URL is rejected.
+pub fn resolve_location(location: &str) -> io::Result<PathBuf> {
+    if location.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "no location given"));
+    }
+
+    if let Some(path) = location.strip_prefix("file://") {
+        // `file://localhost/path` is as valid as `file:///path`, but no other host is
+        let path = path.strip_prefix("localhost").unwrap_or(path);
+        if !path.starts_with('/') {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} isn't a local file", location)));
+        }
+
+        return Ok(PathBuf::from(path));
+    }
+
+    match location.split_once("://") {
+        Some((scheme, _)) if !scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)) => {
+            Err(io::Error::new(io::ErrorKind::Unsupported, format!("{} URLs aren't supported", scheme)))
+        },
+        _ => Ok(PathBuf::from(location))
+    }
+}
+
+/// Formats a digest the way `sha256sum` does, in lowercase hex.
+pub fn hex(digest: &[u8]) -> String {
+    digest.iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Whether `sha256` looks like a SHA-256 digest, written in hex.
+pub fn is_sha256(sha256: &str) -> bool {
+    sha256.len() == 64 && sha256.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Copies the file at `location` to `dest`, checking it against `sha256` on the way. A file which doesn't match isn't
+/// left at `dest`.
+pub fn install_artifact(location: &str, sha256: Option<&str>, dest: &Path) -> Result<(), crate::Error> {
+    let src = resolve_location(location)?;
+    let mut input = File::open(&src)?;
+    let mut output = File::create(dest)?;
+    output.set_permissions(input.metadata()?.permissions())?;
+
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 16];
+    loop {
+        let read = input.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..read]);
+        output.write_all(&buffer[..read])?;
+    }
+
+    let Some(expected) = sha256 else { return Ok(()) };
+    let actual = hex(&hasher.finalize());
+
+    if !actual.eq_ignore_ascii_case(expected) {
+        drop(output);
+        std::fs::remove_file(dest)?;
+        return Err(crate::Error::from(ChecksumError { location: location.to_owned(), expected: expected.to_owned(), actual }));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::build::*;
+    use crate::build::testing::*;
+    use crate::Error;
+
+    #[test]
+    pub fn resolves_locations() {
+        assert_eq!(resolve_location("bin/init").unwrap(), PathBuf::from("bin/init"));
+        assert_eq!(resolve_location("/opt/init").unwrap(), PathBuf::from("/opt/init"));
+        assert_eq!(resolve_location("file:///opt/init").unwrap(), PathBuf::from("/opt/init"));
+        assert_eq!(resolve_location("file://localhost/opt/init").unwrap(), PathBuf::from("/opt/init"));
+        assert!(resolve_location("file://example.com/opt/init").is_err());
+        assert!(resolve_location("https://example.com/init").is_err());
+        assert!(resolve_location("").is_err());
+    }
+
+    #[test]
+    pub fn checks_checksums() {
+        let dir = TempDir::new("artifact");
+        let src = dir.join("hello");
+        let dest = dir.join("installed");
+        fs::write(&src, "hello\n").unwrap();
+        let location = format!("file://{}", src.display());
+
+        let sha256 = "5891B5B522D5DF086D0FF0B110FBD9D21BB4FC7163AF34D08286A2E846F6BE03";
+        install_artifact(&location, Some(sha256), &dest).unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "hello\n");
+
+        let wrong = "0".repeat(64);
+        match install_artifact(&location, Some(&wrong), &dest) {
+            Err(Error::ChecksumError(err)) => {
+                assert_eq!(err.expected, wrong);
+                assert_eq!(err.actual, sha256.to_ascii_lowercase());
+            },
+            other => panic!("expected a checksum error, got {:?}", other)
+        }
+        assert!(!dest.exists());
+    }
+}
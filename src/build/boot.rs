@@ -0,0 +1,103 @@
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use crate::build::*;
+
+/// The part of the MBR before the partition table, which is where the first stage of a BIOS bootloader lives.
+const BOOT_CODE: usize = 446;
+
+/// Writes a BIOS bootloader into the image: its first sector goes into the MBR (keeping the partition table and boot
+/// signature already there), and the rest into the gap between the primary partition table and the first partition.
+pub fn install_bios(image: &Path, layout: &Layout, bootloader: &Path) -> io::Result<()> {
+    let bootloader = fs::read(bootloader)?;
+    let (stage1, stage2) = bootloader.split_at(bootloader.len().min(SECTOR_SIZE as usize));
+
+    let gap = layout.partitions.iter()
+        .map(|i| i.offset)
+        .min()
+        .unwrap_or(layout.image_size - TABLE_TAIL) - FIRST_USABLE;
+
+    if stage2.len() as u64 > gap {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("the BIOS bootloader needs {} after the partition table, but the first partition leaves only {}", format_size(stage2.len() as u64), format_size(gap))));
+    }
+
+    Region::open(image, Extent { offset: 0, size: BOOT_CODE as u64 })?
+        .write_all(&stage1[..stage1.len().min(BOOT_CODE)])?;
+    Region::open(image, Extent { offset: FIRST_USABLE, size: gap })?
+        .write_all(stage2)
+}
+
+/// Formats `region` as an EFI system partition holding `bootloader` as the default x86_64 loader, along with anything
+/// else in `staging`.
+pub fn install_uefi(region: Region, label: &str, staging: &Path, bootloader: &Path) -> io::Result<()> {
+    let dest = staging.join("EFI").join("BOOT");
+    fs::create_dir_all(&dest)?;
+    fs::copy(bootloader, dest.join("BOOTX64.EFI"))?;
+
+    write_fat32(region, label, staging)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::fs::File;
+    use std::io::Read;
+
+    use fatfs::FileSystem;
+    use fatfs::FsOptions;
+
+    use crate::build::*;
+    use crate::build::testing::*;
+
+    fn boot_config(bios: Option<&str>, uefi: Option<&str>) -> BuildConfig {
+        let boot = PartitionPreset::Boot {
+            bios: bios.map(Into::into),
+            uefi: uefi.map(Into::into),
+            post_run: Command { cmd: vec![], cwd: None, env: None }
+        };
+
+        config(vec![partition("boot", boot, None, Some(36 << 20))], None)
+    }
+
+    #[test]
+    pub fn installs_bios_bootloader() {
+        let dir = TempDir::new("bios");
+        let bootloader = (0..2048u32).map(|i| (i % 251) as u8 + 1).collect::<Vec<_>>();
+        fs::write(dir.join("bootloader.bios"), &bootloader).unwrap();
+
+        let config = boot_config(Some("bootloader.bios"), None);
+        let layout = Layout::new(&config).unwrap();
+        let image = dir.join("image.img");
+        write_gpt(&mut File::create(&image).unwrap(), &config, &layout).unwrap();
+        install_bios(&image, &layout, &dir.join("bootloader.bios")).unwrap();
+
+        let content = fs::read(&image).unwrap();
+        assert_eq!(&content[..446], &bootloader[..446]);
+        assert_eq!(content[450], 0xEE);
+        assert_eq!(&content[510..512], &[0x55, 0xAA]);
+        assert_eq!(&content[512..520], b"EFI PART");
+
+        let gap = FIRST_USABLE as usize;
+        assert_eq!(&content[gap..gap + 1536], &bootloader[512..]);
+    }
+
+    #[test]
+    pub fn installs_uefi_bootloader() {
+        let dir = TempDir::new("uefi");
+        fs::create_dir_all(dir.join("staging")).unwrap();
+        fs::write(dir.join("bootloader.efi"), b"MZ").unwrap();
+
+        let config = boot_config(None, Some("bootloader.efi"));
+        let layout = Layout::new(&config).unwrap();
+        let image = dir.join("image.img");
+        write_gpt(&mut File::create(&image).unwrap(), &config, &layout).unwrap();
+        install_uefi(Region::open(&image, layout.partitions[0]).unwrap(), "boot", &dir.join("staging"), &dir.join("bootloader.efi")).unwrap();
+
+        let filesystem = FileSystem::new(Region::open(&image, layout.partitions[0]).unwrap(), FsOptions::new()).unwrap();
+        let mut content = vec![];
+        filesystem.root_dir().open_file("EFI/BOOT/BOOTX64.EFI").unwrap().read_to_end(&mut content).unwrap();
+        assert_eq!(content, b"MZ");
+    }
+}
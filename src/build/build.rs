@@ -1,3 +1,243 @@
-pub fn build() {
-    
-}
\ No newline at end of file
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::build::*;
+use crate::Error;
+
+#[derive(Debug, Clone)]
+pub enum BuildEvent {
+    Queued(Vec<Job>),
+    JobStarted { id: usize, at: Instant },
+    /// A line printed by one of the job's commands.
+    JobOutput { id: usize, line: String },
+    /// The job's result is either `JobStatus::Succeeded` or `JobStatus::Cached`, unless it failed.
+    JobFinished { id: usize, at: Instant, result: Result<JobStatus, Arc<Error>> },
+    Finished(Result<(), Arc<Error>>)
+}
+
+/// Where the cache is kept unless told otherwise.
+pub const CACHE_DIR: &str = "build/cache";
+
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    pub out_dir: PathBuf,
+    /// Where sources fetched from elsewhere, and the outputs of nodes, are kept between builds. This is shared by every
+    /// config.
+    pub cache_dir: PathBuf,
+    /// How many bytes of node outputs the cache may hold. Unlimited if `None`.
+    pub cache_limit: Option<u64>,
+    /// How many jobs may run at the same time.
+    pub jobs: usize
+}
+
+impl BuildOptions {
+    pub fn for_config(config: &BuildConfig) -> Self {
+        Self {
+            out_dir: PathBuf::from("build").join(slug(&config.name)),
+            cache_dir: PathBuf::from(CACHE_DIR),
+            cache_limit: None,
+            jobs: std::thread::available_parallelism()
+                .map(|i| i.get())
+                .unwrap_or(1)
+        }
+    }
+}
+
+/// Lets the caller stop a running build. Jobs that have already started are allowed to finish.
+#[derive(Debug, Clone, Default)]
+pub struct BuildHandle {
+    cancelled: Arc<AtomicBool>
+}
+
+impl BuildHandle {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// Lists the jobs needed to build `config`: one for each `FSNode`, then one for the partition table, followed by one
+/// for each `Partition`.
+pub fn jobs(config: &BuildConfig) -> Vec<Job> {
+    let nodes = config.partitions.iter()
+        .enumerate()
+        .flat_map(|(partition, i)| i.purpose.filesystem()
+            .iter()
+            .map(move |node| JobKind::Node { partition, node: node.clone() }));
+
+    let partitions = config.partitions.iter()
+        .enumerate()
+        .map(|(index, partition)| JobKind::Partition { index, partition: partition.clone() });
+
+    nodes.chain(std::iter::once(JobKind::Table))
+        .chain(partitions)
+        .enumerate()
+        .map(|(id, kind)| Job::new(id, kind, config))
+        .collect()
+}
+
+/// Runs every job of `config`, reporting progress through `on_event`. Configs which don't pass `BuildConfig::validate`
+/// are rejected before anything runs. Jobs are started on a pool of `options.jobs` worker threads as soon as everything
+/// they depend on has finished (see `Schedule`). After a failure no new jobs are started. Blocks until the build
+/// finishes and returns its result, which is also sent as `BuildEvent::Finished`.
+pub fn build<F>(config: BuildConfig, options: BuildOptions, handle: BuildHandle, on_event: F) -> Result<(), Arc<Error>>
+where
+    F: Fn(BuildEvent) + Send + Sync {
+    let jobs = jobs(&config);
+    on_event(BuildEvent::Queued(jobs.clone()));
+
+    let diagnostics = config.validate();
+    let result = match (Schedule::new(&jobs), Layout::new(&config)) {
+        _ if has_errors(&diagnostics) => Err(Arc::new(Error::from(Diagnostics(diagnostics)))),
+        (Ok(schedule), Ok(layout)) => {
+            let workers = options.jobs;
+            let cx = BuildContext::new(config, layout, options, handle.clone());
+            run_jobs(&cx, jobs, schedule, workers, &on_event)
+        },
+        (Err(err), _) => Err(Arc::new(Error::from(err))),
+        (_, Err(err)) => Err(Arc::new(Error::from(err)))
+    };
+
+    on_event(BuildEvent::Finished(result.clone()));
+    result
+}
+
+struct Progress {
+    remaining: Vec<usize>,
+    ready: VecDeque<usize>,
+    running: usize,
+    failure: Option<Arc<Error>>
+}
+
+fn run_jobs<F>(cx: &BuildContext, jobs: Vec<Job>, schedule: Schedule, workers: usize, on_event: &F) -> Result<(), Arc<Error>>
+where
+    F: Fn(BuildEvent) + Send + Sync {
+    // Anything staged by an earlier build, such as the files of nodes since removed from the config or whatever a
+    // `post_run` hook left behind, would otherwise end up in this build's images
+    match std::fs::remove_dir_all(cx.staging_root()) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(Arc::new(Error::from(err))),
+        _ => {}
+    }
+
+    std::fs::create_dir_all(&cx.out_dir).map_err(|err| Arc::new(Error::from(err)))?;
+
+    let progress = Mutex::new(Progress {
+        remaining: schedule.dependencies.iter().map(|i| i.len()).collect(),
+        ready: schedule.roots().collect(),
+        running: 0,
+        failure: None
+    });
+    let changed = Condvar::new();
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers.max(1) {
+            scope.spawn(|| loop {
+                let mut state = progress.lock().unwrap();
+                let id = loop {
+                    if cx.handle.is_cancelled() || state.failure.is_some() {
+                        return;
+                    }
+
+                    match state.ready.pop_front() {
+                        Some(id) => break id,
+                        None if state.running == 0 => return,
+                        None => state = changed.wait(state).unwrap()
+                    }
+                };
+
+                state.running += 1;
+                drop(state);
+
+                let result = run_job(cx, &jobs[id], on_event);
+
+                let mut state = progress.lock().unwrap();
+                state.running -= 1;
+                match result {
+                    Ok(_) => for dependent in &schedule.dependents[id] {
+                        state.remaining[*dependent] -= 1;
+                        if state.remaining[*dependent] == 0 {
+                            state.ready.push_back(*dependent);
+                        }
+                    },
+                    Err(err) => {
+                        state.failure.get_or_insert(err);
+                    }
+                }
+
+                changed.notify_all();
+            });
+        }
+    });
+
+    if let Some(err) = progress.into_inner().unwrap().failure {
+        return Err(err);
+    }
+
+    if cx.handle.is_cancelled() {
+        return Err(Arc::new(Error::Cancelled));
+    }
+
+    Ok(())
+}
+
+fn run_job<F>(cx: &BuildContext, job: &Job, on_event: &F) -> Result<JobStatus, Arc<Error>>
+where
+    F: Fn(BuildEvent) + Send + Sync {
+    on_event(BuildEvent::JobStarted { id: job.id, at: Instant::now() });
+
+    let result = job.run(cx, &|line| on_event(BuildEvent::JobOutput { id: job.id, line: line.to_owned() }))
+        .map_err(Arc::new);
+
+    on_event(BuildEvent::JobFinished { id: job.id, at: Instant::now(), result: result.clone() });
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use fatfs::FileSystem;
+    use fatfs::FsOptions;
+
+    use crate::build::*;
+    use crate::build::testing::*;
+
+    #[test]
+    pub fn clears_staging_between_builds() {
+        let dir = TempDir::new("build");
+        fs::write(dir.join("hostname"), "redox\n").unwrap();
+
+        let node = |path: &str| FSNode {
+            path: PathBuf::from(path),
+            source: NodeSource::Static { location: dir.join("hostname").display().to_string(), sha256: None },
+            dependencies: None
+        };
+        let efi = |filesystem| config(vec![partition("efi", PartitionPreset::Fat32 { filesystem, post_run: None }, None, Some(36 << 20))], None);
+        let options = BuildOptions { out_dir: dir.join("out"), cache_dir: dir.join("cache"), cache_limit: None, jobs: 1 };
+
+        let files = || {
+            let config = efi(vec![]);
+            let cx = BuildContext::new(config.clone(), Layout::new(&config).unwrap(), options.clone(), BuildHandle::default());
+            let filesystem = FileSystem::new(Region::open(&cx.image, cx.layout.partitions[0]).unwrap(), FsOptions::new()).unwrap();
+            let mut files = filesystem.root_dir().iter().map(|i| i.unwrap().file_name()).collect::<Vec<_>>();
+            files.sort();
+            files
+        };
+
+        build(efi(vec![node("/hostname"), node("/motd")]), options.clone(), BuildHandle::default(), |_| {}).unwrap();
+        assert_eq!(files(), ["hostname", "motd"]);
+
+        build(efi(vec![node("/hostname")]), options.clone(), BuildHandle::default(), |_| {}).unwrap();
+        assert_eq!(files(), ["hostname"]);
+    }
+}
@@ -0,0 +1,264 @@
+use std::cell::RefCell;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::build::*;
+use crate::Error;
+
+/// The directory node outputs are kept in, named after their keys.
+fn nodes_dir(cache_dir: &Path) -> PathBuf {
+    cache_dir.join("nodes")
+}
+
+pub fn file_sha256(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Asks `repo` which commit `origin` currently points to, without fetching anything.
+fn resolve_commit(cx: &BuildContext, repo: &str, origin: &GitOrigin, log: &dyn Fn(&str)) -> Result<String, Error> {
+    let reference = match origin {
+        GitOrigin::Head => "HEAD",
+        GitOrigin::Branch(branch) => branch,
+        GitOrigin::Commit(commit) => return Ok(commit.clone())
+    };
+
+    // Only lines of the form "<commit>\t<ref>" are output, anything else is git talking on stderr
+    let commits = RefCell::new(vec![]);
+    let mut command = process::Command::new("git");
+    command.args(["ls-remote", repo, reference]);
+    run_command(command, &cx.handle, &|line| match line.split_once('\t') {
+        Some((commit, _)) => commits.borrow_mut().push(commit.to_owned()),
+        None => log(line)
+    })?;
+
+    commits.into_inner()
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::from(io::Error::new(io::ErrorKind::NotFound, format!("{} has no {}", repo, reference))))
+}
+
+/// Identifies what building `node` would produce: its source, whatever the source currently resolves to, and the
+/// staged outputs of everything it depends on. Nodes with the same key are assumed to build the same file.
+///
+/// Custom commands have no key, and are run every time, since what they produce depends on whatever they read from
+/// their working directory or anywhere else. Neither do nodes depending on something that isn't staged as a file, such
+/// as a directory a custom command made.
+pub fn node_key(cx: &BuildContext, node: &FSNode, log: &dyn Fn(&str)) -> Result<Option<String>, Error> {
+    let mut hasher = Sha256::new();
+
+    // Going through `Value` sorts the keys of maps such as `Command::env`, so equal sources always hash the same
+    hasher.update(serde_json::to_value(&node.source)?.to_string());
+
+    match &node.source {
+        NodeSource::Static { location, .. } | NodeSource::Prebuilt { location, .. } => {
+            hasher.update(file_sha256(&resolve_location(location)?)?);
+        },
+        NodeSource::Cargo { repo, origin, .. } => {
+            hasher.update(resolve_commit(cx, repo, &origin.clone().unwrap_or_default(), log)?);
+        },
+        NodeSource::Custom(_) => return Ok(None)
+    }
+
+    for dependency in node.dependencies.iter().flatten() {
+        for (index, partition) in cx.config.partitions.iter().enumerate() {
+            if partition.purpose.filesystem().iter().any(|i| i.path == *dependency) {
+                let staged = cx.staged_path(index, dependency);
+                if !staged.is_file() {
+                    return Ok(None);
+                }
+
+                hasher.update(file_sha256(&staged)?);
+            }
+        }
+    }
+
+    Ok(Some(hex(&hasher.finalize())))
+}
+
+/// Copies the output cached under `key` to `dest`. Returns false if there isn't one.
+pub fn restore_node(cx: &BuildContext, key: &str, dest: &Path) -> io::Result<bool> {
+    let cached = nodes_dir(&cx.cache_dir).join(key);
+    if !cached.is_file() {
+        return Ok(false);
+    }
+
+    fs::copy(&cached, dest)?;
+
+    // Eviction goes by modification time, so this keeps the entry from being the next to go
+    File::options().append(true).open(&cached)?.set_modified(SystemTime::now())?;
+    Ok(true)
+}
+
+/// Keeps a copy of `output` under `key`, then evicts the least recently used entries until the cache fits in
+/// `cx.cache_limit`.
+pub fn store_node(cx: &BuildContext, key: &str, output: &Path) -> io::Result<()> {
+    let dir = nodes_dir(&cx.cache_dir);
+    fs::create_dir_all(&dir)?;
+
+    // Copied under a temporary name first, so that an interrupted copy is never mistaken for a complete one
+    let partial = dir.join(format!("{}.partial", key));
+    fs::copy(output, &partial)?;
+    fs::rename(&partial, dir.join(key))?;
+
+    match cx.cache_limit {
+        Some(limit) => evict(&cx.cache_dir, limit),
+        None => Ok(())
+    }
+}
+
+/// Removes the least recently used node outputs until the rest take up at most `limit` bytes. Git checkouts aren't
+/// counted, since they're shared between nodes and reused however often their sources change, and neither are copies
+/// still being stored.
+pub fn evict(cache_dir: &Path, limit: u64) -> io::Result<()> {
+    // Jobs finishing together would otherwise each count the same entries and evict more than they need to
+    static EVICTING: Mutex<()> = Mutex::new(());
+    let _guard = EVICTING.lock().unwrap();
+
+    let mut entries = vec![];
+    for entry in fs::read_dir(nodes_dir(cache_dir))? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().is_some_and(|i| i == "partial") {
+            continue;
+        }
+
+        // Anything which disappears in the meantime, say because another build evicted it, is simply left out
+        match entry.metadata() {
+            Ok(metadata) if metadata.is_file() => entries.push((metadata.modified()?, metadata.len(), path)),
+            Ok(_) => {},
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {},
+            Err(err) => return Err(err)
+        }
+    }
+
+    entries.sort();
+
+    let mut size = entries.iter().map(|i| i.1).sum::<u64>();
+    for (_, len, path) in entries {
+        if size <= limit {
+            break;
+        }
+
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => size -= len
+        }
+    }
+
+    Ok(())
+}
+
+/// Deletes everything in the cache, including git checkouts.
+pub fn clear_cache(cache_dir: &Path) -> io::Result<()> {
+    match fs::remove_dir_all(cache_dir) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::build::*;
+    use crate::build::testing::*;
+
+    #[test]
+    pub fn reuses_unchanged_nodes() {
+        let dir = TempDir::new("cache");
+        fs::write(dir.join("hostname"), "redox\n").unwrap();
+
+        let node = |path: &str, dependencies: Option<Vec<PathBuf>>| FSNode {
+            path: PathBuf::from(path),
+            source: NodeSource::Static { location: dir.join("hostname").display().to_string(), sha256: None },
+            dependencies
+        };
+        let root = PartitionPreset::Ext4 {
+            filesystem: vec![node("/etc/hostname", None), node("/etc/motd", Some(vec![PathBuf::from("/etc/hostname")]))],
+            post_run: None
+        };
+        let config = config(vec![partition("root", root, None, Some(1 << 20))], None);
+        let options = BuildOptions { out_dir: dir.join("out"), cache_dir: dir.join("cache"), cache_limit: Some(10), jobs: 1 };
+        let cx = BuildContext::new(config.clone(), Layout::new(&config).unwrap(), options, BuildHandle::default());
+
+        let hostname = &config.partitions[0].purpose.filesystem()[0];
+        let key = node_key(&cx, hostname, &|_| {}).unwrap().unwrap();
+        assert_eq!(Some(key.clone()), node_key(&cx, hostname, &|_| {}).unwrap());
+
+        let dest = dir.join("restored");
+        assert!(!restore_node(&cx, &key, &dest).unwrap());
+        store_node(&cx, &key, &dir.join("hostname")).unwrap();
+        assert!(restore_node(&cx, &key, &dest).unwrap());
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "redox\n");
+
+        // The key follows the contents of the source, and of the staged dependencies
+        let motd = &config.partitions[0].purpose.filesystem()[1];
+        let staged = cx.staged_path(0, hostname.path.as_path());
+        fs::create_dir_all(staged.parent().unwrap()).unwrap();
+        fs::write(&staged, "redox\n").unwrap();
+        let before = node_key(&cx, motd, &|_| {}).unwrap();
+        fs::write(&staged, "other\n").unwrap();
+        assert_ne!(before, node_key(&cx, motd, &|_| {}).unwrap());
+
+        fs::write(dir.join("hostname"), "changed\n").unwrap();
+        assert_ne!(Some(key.clone()), node_key(&cx, hostname, &|_| {}).unwrap());
+
+        // Nothing can say whether a command would make the same file again
+        let custom = FSNode { path: PathBuf::from("/etc/issue"), source: NodeSource::Custom(Command { cmd: vec!["true".to_owned()], cwd: None, env: None }), dependencies: None };
+        assert_eq!(node_key(&cx, &custom, &|_| {}).unwrap(), None);
+
+        // At 10 bytes, the cache only has room for one entry
+        store_node(&cx, "other", &dir.join("hostname")).unwrap();
+        assert!(!restore_node(&cx, &key, &dest).unwrap());
+        assert!(restore_node(&cx, "other", &dest).unwrap());
+
+        // Copies still being stored are left alone
+        let partial = dir.join("cache/nodes/copying.partial");
+        fs::write(&partial, "a partial copy\n").unwrap();
+        evict(&dir.join("cache"), 10).unwrap();
+        assert!(partial.exists());
+        assert!(restore_node(&cx, "other", &dest).unwrap());
+
+        clear_cache(&dir.join("cache")).unwrap();
+        assert!(!dir.join("cache").exists());
+    }
+
+    #[test]
+    pub fn skips_nodes_depending_on_directories() {
+        let dir = TempDir::new("cache-dirs");
+        fs::write(dir.join("hostname"), "redox\n").unwrap();
+
+        let generated = FSNode {
+            path: PathBuf::from("/usr/share/generated"),
+            source: NodeSource::Custom(Command { cmd: vec!["true".to_owned()], cwd: None, env: None }),
+            dependencies: None
+        };
+        let hostname = FSNode {
+            path: PathBuf::from("/etc/hostname"),
+            source: NodeSource::Static { location: dir.join("hostname").display().to_string(), sha256: None },
+            dependencies: Some(vec![generated.path.clone()])
+        };
+        let root = PartitionPreset::Ext4 { filesystem: vec![generated.clone(), hostname.clone()], post_run: None };
+        let config = config(vec![partition("root", root, None, Some(1 << 20))], None);
+        let options = BuildOptions { out_dir: dir.join("out"), cache_dir: dir.join("cache"), cache_limit: None, jobs: 1 };
+        let cx = BuildContext::new(config.clone(), Layout::new(&config).unwrap(), options, BuildHandle::default());
+
+        // Nothing has been staged yet
+        assert_eq!(node_key(&cx, &hostname, &|_| {}).unwrap(), None);
+
+        fs::create_dir_all(cx.staged_path(0, &generated.path)).unwrap();
+        assert_eq!(node_key(&cx, &hostname, &|_| {}).unwrap(), None);
+    }
+}
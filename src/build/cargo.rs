@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::build::*;
+use crate::Error;
+
+/// Nodes built from the same repository share a checkout, which can only be at one revision at a time.
+fn checkout_lock(checkout: &Path) -> Arc<Mutex<()>> {
+    static LOCKS: OnceLock<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> = OnceLock::new();
+
+    LOCKS.get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .entry(checkout.to_owned())
+        .or_default()
+        .clone()
+}
+
+/// Where `repo` is checked out. Different URLs can slug to the same name, so a hash of the URL keeps them apart while
+/// the slug keeps the directory recognisable.
+fn checkout_dir(cx: &BuildContext, repo: &str) -> PathBuf {
+    let hash = hex(&Sha256::digest(repo.as_bytes()));
+    cx.cache_dir.join("git").join(format!("{}-{}", slug(repo), &hash[..16]))
+}
+
+/// Fetches `repo` into the cache, checks out `origin` and runs `cargo build --release` in it. The binary named after
+/// the last component of `dest` is then copied to `dest`.
+pub fn build_cargo(cx: &BuildContext, source: &NodeSource, dest: &Path, log: &dyn Fn(&str)) -> Result<(), Error> {
+    let NodeSource::Cargo { repo, origin, target, features } = source else {
+        return Err(Error::from(io::Error::new(io::ErrorKind::InvalidInput, "not a Cargo source")));
+    };
+
+    let checkout = checkout_dir(cx, repo);
+    let lock = checkout_lock(&checkout);
+    let _guard = lock.lock().unwrap();
+
+    let git = |args: &[&str]| {
+        let mut command = process::Command::new("git");
+        command.arg("-C").arg(&checkout).args(args);
+        run_command(command, &cx.handle, log)
+    };
+
+    if !checkout.join(".git").exists() {
+        fs::create_dir_all(&checkout)?;
+        git(&["init", "--quiet"])?;
+        git(&["remote", "add", "origin", repo])?;
+    }
+
+    match origin.clone().unwrap_or_default() {
+        GitOrigin::Head => {
+            git(&["fetch", "origin", "HEAD"])?;
+            git(&["checkout", "--force", "--detach", "FETCH_HEAD"])?;
+        },
+        GitOrigin::Branch(branch) => {
+            git(&["fetch", "origin", &branch])?;
+            git(&["checkout", "--force", "--detach", "FETCH_HEAD"])?;
+        },
+        GitOrigin::Commit(commit) => {
+            // Not every server lets a commit be fetched by itself, nor can an abbreviated one be, in which case
+            // everything is fetched and the commit looked up locally
+            match git(&["fetch", "origin", &commit]) {
+                Ok(()) => git(&["checkout", "--force", "--detach", "FETCH_HEAD"])?,
+                Err(Error::Cancelled) => return Err(Error::Cancelled),
+                Err(_) => {
+                    git(&["fetch", "origin"])?;
+                    git(&["checkout", "--force", "--detach", &commit])?;
+                }
+            }
+        }
+    }
+
+    let target_dir = checkout.join("target");
+    let mut cargo = process::Command::new("cargo");
+    cargo.args(["build", "--release"])
+        .arg("--manifest-path").arg(checkout.join("Cargo.toml"))
+        .arg("--target-dir").arg(&target_dir);
+
+    if let Some(target) = target {
+        cargo.args(["--target", target]);
+    }
+
+    if let Some(features) = features.as_ref().filter(|i| !i.is_empty()) {
+        cargo.arg("--features").arg(features.join(","));
+    }
+
+    run_command(cargo, &cx.handle, log)?;
+
+    let name = dest.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} doesn't name a binary", dest.display())))?;
+    let binary = match target {
+        Some(target) => target_dir.join(target),
+        None => target_dir
+    }.join("release").join(name);
+
+    if !binary.is_file() {
+        return Err(Error::from(io::Error::new(io::ErrorKind::NotFound, format!("building {} didn't produce a binary called {}", repo, name.to_string_lossy()))));
+    }
+
+    fs::copy(binary, dest)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::process;
+
+    use crate::build::*;
+    use crate::build::testing::*;
+
+    use super::checkout_dir;
+
+    fn git(dir: &std::path::Path, args: &[&str]) {
+        let status = process::Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com", "-C"])
+            .arg(dir)
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[test]
+    pub fn builds_local_repository() {
+        let dir = TempDir::new("cargo");
+        let repo = dir.join("hello");
+        fs::create_dir_all(repo.join("src")).unwrap();
+        fs::write(repo.join("Cargo.toml"), "[package]\nname = \"hello\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n").unwrap();
+        fs::write(repo.join("src/main.rs"), "fn main() { println!(\"hello\"); }\n").unwrap();
+        git(&repo, &["init", "--quiet", "--initial-branch=main"]);
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "--quiet", "-m", "Hello"]);
+
+        let config = config(vec![], None);
+        let options = BuildOptions { out_dir: dir.join("out"), cache_dir: dir.join("cache"), cache_limit: None, jobs: 1 };
+        let cx = BuildContext::new(config.clone(), Layout::new(&config).unwrap(), options, BuildHandle::default());
+
+        let source = NodeSource::Cargo {
+            repo: format!("file://{}", repo.display()),
+            origin: Some(GitOrigin::Branch("main".to_owned())),
+            target: None,
+            features: None
+        };
+        // These slug to the same name
+        assert_ne!(checkout_dir(&cx, "https://example.com/a/b"), checkout_dir(&cx, "https://example.com/a-b"));
+
+        fs::create_dir_all(dir.join("out")).unwrap();
+        build_cargo(&cx, &source, &dir.join("out/hello"), &|_| {}).unwrap();
+
+        let output = process::Command::new(dir.join("out/hello")).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "hello\n");
+
+        // A later commit doesn't change what an earlier one builds
+        let first = process::Command::new("git").arg("-C").arg(&repo).args(["rev-parse", "HEAD"]).output().unwrap();
+        let first = String::from_utf8(first.stdout).unwrap().trim().to_owned();
+        fs::write(repo.join("src/main.rs"), "fn main() { println!(\"goodbye\"); }\n").unwrap();
+        git(&repo, &["commit", "--quiet", "-am", "Goodbye"]);
+
+        let source = NodeSource::Cargo {
+            repo: format!("file://{}", repo.display()),
+            origin: Some(GitOrigin::Commit(first)),
+            target: None,
+            features: None
+        };
+        build_cargo(&cx, &source, &dir.join("out/hello"), &|_| {}).unwrap();
+
+        let output = process::Command::new(dir.join("out/hello")).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "hello\n");
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::process;
+use std::process::ExitStatus;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::build::*;
+use crate::Error;
+
+/// How many lines of stderr a `CommandError` keeps.
+const STDERR_TAIL: usize = 20;
+
+/// A command which ran to completion, but didn't succeed.
+#[derive(Debug)]
+pub struct CommandError {
+    pub program: String,
+    pub status: ExitStatus,
+    /// The last lines the command wrote to stderr.
+    pub stderr: Vec<String>
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} exited with {}", self.program, self.status)?;
+        for line in &self.stderr {
+            write!(f, "\n    {}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Command {
+    /// Builds the process to run, with `env` added to the environment of this one. Returns `None` if `cmd` is empty.
+    pub fn process(&self) -> Option<process::Command> {
+        let (program, args) = self.cmd.split_first()?;
+
+        let mut command = process::Command::new(program);
+        command.args(args);
+
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+
+        if let Some(env) = &self.env {
+            command.envs(env);
+        }
+
+        Some(command)
+    }
+
+    /// The command line as it's shown for editing, with arguments quoted where a shell would need them to be.
+    pub fn command_line(&self) -> String {
+        shell_words::join(&self.cmd)
+    }
+
+    /// Splits a command line written the way `command_line` shows it. Returns `None` if a quote is left open.
+    pub fn parse_command_line(line: &str) -> Option<Vec<String>> {
+        shell_words::split(line).ok()
+    }
+}
+
+/// Runs `command`, passing each line it writes to stdout or stderr to `log` as it arrives. The command is killed if
+/// the build is cancelled, though anything it started in turn is left running.
+pub fn run_command(mut command: process::Command, handle: &BuildHandle, log: &dyn Fn(&str)) -> Result<(), Error> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let (sender, receiver) = mpsc::channel();
+    let stdout = child.stdout.take().map(|i| (false, Box::new(i) as Box<dyn Read + Send>));
+    let stderr = child.stderr.take().map(|i| (true, Box::new(i) as Box<dyn Read + Send>));
+
+    // The readers aren't joined, since anything the command started in the background can keep its pipes open after
+    // it's been killed
+    for (is_stderr, stream) in stdout.into_iter().chain(stderr) {
+        let sender = sender.clone();
+        std::thread::spawn(move || for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            if sender.send((is_stderr, line)).is_err() {
+                break;
+            }
+        });
+    }
+    drop(sender);
+
+    let mut tail = VecDeque::with_capacity(STDERR_TAIL);
+    let cancelled = loop {
+        match receiver.recv_timeout(Duration::from_millis(100)) {
+            Ok((is_stderr, line)) => {
+                log(&line);
+
+                if is_stderr {
+                    if tail.len() == STDERR_TAIL {
+                        tail.pop_front();
+                    }
+                    tail.push_back(line);
+                }
+            },
+            Err(mpsc::RecvTimeoutError::Timeout) => {},
+            Err(mpsc::RecvTimeoutError::Disconnected) => break false
+        }
+
+        if handle.is_cancelled() {
+            child.kill().ok();
+            break true;
+        }
+    };
+
+    let status = child.wait()?;
+    if cancelled {
+        return Err(Error::Cancelled);
+    }
+
+    if !status.success() {
+        return Err(Error::from(CommandError { program, status, stderr: tail.into() }));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use crate::build::*;
+    use crate::Error;
+
+    fn shell(script: &str) -> process::Command {
+        Command { cmd: vec!["sh".to_owned(), "-c".to_owned(), script.to_owned()], cwd: None, env: None }
+            .process()
+            .unwrap()
+    }
+
+    #[test]
+    pub fn quotes_command_lines() {
+        let command = Command { cmd: vec!["sh".to_owned(), "-c".to_owned(), "make all".to_owned()], cwd: None, env: None };
+        assert_eq!(command.command_line(), "sh -c 'make all'");
+        assert_eq!(Command::parse_command_line(&command.command_line()), Some(command.cmd.clone()));
+        assert_eq!(Command::parse_command_line("sh -c \"make"), None);
+
+        let source = NodeSource::Custom(command);
+        assert_eq!(source.with_kind(SourceKind::Static).with_kind(SourceKind::Custom), source);
+    }
+
+    #[test]
+    pub fn captures_output() {
+        let lines = Mutex::new(vec![]);
+        let log = |line: &str| lines.lock().unwrap().push(line.to_owned());
+
+        run_command(shell("echo one; echo two >&2"), &BuildHandle::default(), &log).unwrap();
+        let mut lines = lines.into_inner().unwrap();
+        lines.sort();
+        assert_eq!(lines, vec!["one", "two"]);
+
+        match run_command(shell("echo out; echo first >&2; echo last >&2; exit 3"), &BuildHandle::default(), &|_| {}) {
+            Err(Error::CommandError(err)) => {
+                assert_eq!(err.program, "sh");
+                assert_eq!(err.status.code(), Some(3));
+                assert_eq!(err.stderr, vec!["first", "last"]);
+                assert!(Error::CommandError(err).to_string().starts_with("sh exited with"));
+            },
+            other => panic!("expected a command error, got {:?}", other)
+        }
+    }
+
+    #[test]
+    pub fn kills_cancelled_commands() {
+        let handle = BuildHandle::default();
+        handle.cancel();
+
+        let started = Instant::now();
+        assert!(matches!(run_command(shell("sleep 10"), &handle, &|_| {}), Err(Error::Cancelled)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
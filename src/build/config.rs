@@ -24,15 +24,84 @@ pub enum GitOrigin {
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum NodeSource {
-    Static(String),
-    Prebuilt(String),
+    /// A file checked in alongside the config. `location` is a local path or a `file://` URL, and `sha256`, if given,
+    /// is checked before the file is installed.
+    Static {
+        location: String,
+        sha256: Option<String>
+    },
+    /// A binary built elsewhere, found and checked the same way as a `Static` file.
+    Prebuilt {
+        location: String,
+        sha256: Option<String>
+    },
     Cargo {
         repo: String,
-        origin: Option<GitOrigin>
+        origin: Option<GitOrigin>,
+        /// The target triple to build for, such as `x86_64-unknown-redox`. Defaults to the host.
+        target: Option<String>,
+        features: Option<Vec<String>>
     },
     Custom(Command)
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    Static,
+    Prebuilt,
+    Cargo,
+    Custom
+}
+
+impl SourceKind {
+    pub const ALL: [SourceKind; 4] = [SourceKind::Static, SourceKind::Prebuilt, SourceKind::Cargo, SourceKind::Custom];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SourceKind::Static => "Static",
+            SourceKind::Prebuilt => "Prebuilt",
+            SourceKind::Cargo => "Cargo",
+            SourceKind::Custom => "Custom"
+        }
+    }
+}
+
+impl NodeSource {
+    pub fn kind(&self) -> SourceKind {
+        match self {
+            NodeSource::Static { .. } => SourceKind::Static,
+            NodeSource::Prebuilt { .. } => SourceKind::Prebuilt,
+            NodeSource::Cargo { .. } => SourceKind::Cargo,
+            NodeSource::Custom(_) => SourceKind::Custom
+        }
+    }
+
+    /// Converts the source to another kind, carrying over its location (or command line), and its checksum, as far as
+    /// possible.
+    pub fn with_kind(&self, kind: SourceKind) -> Self {
+        if kind == self.kind() {
+            return self.clone();
+        }
+
+        let (location, sha256) = match self {
+            NodeSource::Static { location, sha256 } | NodeSource::Prebuilt { location, sha256 } => (location.clone(), sha256.clone()),
+            NodeSource::Cargo { repo, .. } => (repo.clone(), None),
+            NodeSource::Custom(cmd) => (cmd.command_line(), None)
+        };
+
+        match kind {
+            SourceKind::Static => NodeSource::Static { location, sha256 },
+            SourceKind::Prebuilt => NodeSource::Prebuilt { location, sha256 },
+            SourceKind::Cargo => NodeSource::Cargo { repo: location, origin: None, target: None, features: None },
+            SourceKind::Custom => NodeSource::Custom(Command {
+                cmd: Command::parse_command_line(&location).unwrap_or_else(|| vec![location]),
+                cwd: None,
+                env: None
+            })
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct FSNode {
     pub path: PathBuf,
@@ -54,13 +123,126 @@ pub enum PartitionPreset {
         filesystem: Vec<FSNode>,
         post_run: Option<Command>
     },
+    /// Installs the bootloaders given. With a UEFI bootloader the partition becomes an EFI system partition, otherwise
+    /// it's left empty as a BIOS boot partition. `post_run` can still do anything the bootloaders don't cover, but runs
+    /// before they're installed.
     Boot {
+        /// Written into the MBR and the gap between the partition table and the first partition.
+        bios: Option<PathBuf>,
+        /// Installed as `EFI/BOOT/BOOTX64.EFI`.
+        uefi: Option<PathBuf>,
         post_run: Command
     },
     Swap,
+    /// Written as-is: `source` is copied to the start of the partition, and the rest is filled with `fill`, which
+    /// defaults to zero.
+    Raw {
+        source: Option<PathBuf>,
+        fill: Option<u8>
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresetKind {
+    Ext4,
+    Fat32,
+    RedoxFS,
+    Boot,
+    Swap,
     Raw
 }
 
+impl PresetKind {
+    pub const ALL: [PresetKind; 6] = [PresetKind::Ext4, PresetKind::Fat32, PresetKind::RedoxFS, PresetKind::Boot, PresetKind::Swap, PresetKind::Raw];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PresetKind::Ext4 => "ext4",
+            PresetKind::Fat32 => "FAT32",
+            PresetKind::RedoxFS => "RedoxFS",
+            PresetKind::Boot => "Boot",
+            PresetKind::Swap => "Swap",
+            PresetKind::Raw => "Raw"
+        }
+    }
+
+    pub fn has_filesystem(&self) -> bool {
+        matches!(self, PresetKind::Ext4 | PresetKind::Fat32 | PresetKind::RedoxFS)
+    }
+}
+
+impl PartitionPreset {
+    pub fn kind(&self) -> PresetKind {
+        match self {
+            PartitionPreset::Ext4 { .. } => PresetKind::Ext4,
+            PartitionPreset::Fat32 { .. } => PresetKind::Fat32,
+            PartitionPreset::RedoxFS { .. } => PresetKind::RedoxFS,
+            PartitionPreset::Boot { .. } => PresetKind::Boot,
+            PartitionPreset::Swap => PresetKind::Swap,
+            PartitionPreset::Raw { .. } => PresetKind::Raw
+        }
+    }
+
+    /// The command run once the partition's nodes are staged, just before the partition is written into the image, so
+    /// anything it changes in the staging directory ends up in the partition. It's told where things are through
+    /// `GUIBUILD_STAGING`, `GUIBUILD_LABEL`, `GUIBUILD_OFFSET`, `GUIBUILD_SIZE` (both in bytes) and `GUIBUILD_IMAGE`.
+    ///
+    /// For a boot partition this is also before the bootloaders are installed. Files the command stages are written to
+    /// the EFI system partition alongside the UEFI bootloader, while anything it writes straight into the image where
+    /// the partition or the BIOS bootloader go is overwritten.
+    pub fn post_run(&self) -> Option<&Command> {
+        match self {
+            PartitionPreset::Ext4 { post_run, .. }
+                | PartitionPreset::Fat32 { post_run, .. }
+                | PartitionPreset::RedoxFS { post_run, .. } => post_run.as_ref(),
+            PartitionPreset::Boot { post_run, .. } => Some(post_run),
+            _ => None
+        }
+    }
+
+    /// Converts the preset to another kind, keeping its filesystem and `post_run` command wherever the new kind has
+    /// room for them.
+    pub fn with_kind(&self, kind: PresetKind) -> Self {
+        if kind == self.kind() {
+            return self.clone();
+        }
+
+        let filesystem = self.filesystem().to_vec();
+        let post_run = self.post_run().cloned();
+
+        match kind {
+            PresetKind::Ext4 => PartitionPreset::Ext4 { filesystem, post_run },
+            PresetKind::Fat32 => PartitionPreset::Fat32 { filesystem, post_run },
+            PresetKind::RedoxFS => PartitionPreset::RedoxFS { filesystem, post_run },
+            PresetKind::Boot => PartitionPreset::Boot {
+                bios: None,
+                uefi: None,
+                post_run: post_run.unwrap_or(Command { cmd: vec![], cwd: None, env: None })
+            },
+            PresetKind::Swap => PartitionPreset::Swap,
+            PresetKind::Raw => PartitionPreset::Raw { source: None, fill: None }
+        }
+    }
+
+    pub fn filesystem(&self) -> &[FSNode] {
+        match self {
+            PartitionPreset::Ext4 { filesystem, .. }
+                | PartitionPreset::Fat32 { filesystem, .. }
+                | PartitionPreset::RedoxFS { filesystem, .. } => filesystem,
+            _ => &[]
+        }
+    }
+
+    pub fn filesystem_mut(&mut self) -> Option<&mut Vec<FSNode>> {
+        match self {
+            PartitionPreset::Ext4 { filesystem, .. }
+                | PartitionPreset::Fat32 { filesystem, .. }
+                | PartitionPreset::RedoxFS { filesystem, .. } => Some(filesystem),
+            _ => None
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Partition {
     pub label: String,
@@ -76,6 +258,18 @@ pub struct BuildConfig {
     pub image_size: Option<u64>
 }
 
+impl Data for FSNode {
+    fn same(&self, other: &Self) -> bool {
+        *self == *other
+    }
+}
+
+impl Data for Partition {
+    fn same(&self, other: &Self) -> bool {
+        *self == *other
+    }
+}
+
 impl Data for BuildConfig {
     fn same(&self, other: &Self) -> bool {
         *self == *other
@@ -95,6 +289,8 @@ mod tests {
                 offset: Some(34),
                 size: None,
                 purpose: build::PartitionPreset::Boot {
+                    bios: None,
+                    uefi: None,
                     post_run: build::Command {
                         cmd: vec!["/usr/bin/nu".to_owned(), "-c".to_owned(), "echo hello".to_owned()],
                         cwd: None,
@@ -0,0 +1,102 @@
+use std::fs;
+use std::io;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::path::Path;
+
+use fatfs::FatType;
+use fatfs::FileSystem;
+use fatfs::FormatVolumeOptions;
+use fatfs::FsOptions;
+use fatfs::ReadWriteSeek;
+
+use crate::build::*;
+
+/// The smallest partition fatfs will format as FAT32. FAT32 needs at least 65525 clusters, which at 512 bytes each
+/// (plus the FATs themselves) comes to a little over 32 MiB.
+pub const FAT32_MIN_SIZE: u64 = 33 << 20;
+
+/// FAT volume labels are at most 11 characters of upper case ASCII, padded with spaces.
+fn volume_label(label: &str) -> [u8; 11] {
+    let mut volume_label = [b' '; 11];
+    for (byte, c) in volume_label.iter_mut().zip(label.chars().filter(char::is_ascii_alphanumeric)) {
+        *byte = c.to_ascii_uppercase() as u8;
+    }
+
+    volume_label
+}
+
+/// Formats `region` as FAT32 and copies the contents of `staging` into it, keeping long file names.
+pub fn write_fat32(mut region: Region, label: &str, staging: &Path) -> io::Result<()> {
+    fatfs::format_volume(&mut region, FormatVolumeOptions::new()
+        .fat_type(FatType::Fat32)
+        .volume_label(volume_label(label)))?;
+
+    region.seek(SeekFrom::Start(0))?;
+    let filesystem = FileSystem::new(region, FsOptions::new())?;
+    copy_dir(&filesystem.root_dir(), staging)?;
+    filesystem.unmount()
+}
+
+fn copy_dir<T: ReadWriteSeek>(dir: &fatfs::Dir<T>, src: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(src)? {
+        let path = entry?.path();
+        let name = path.file_name()
+            .and_then(|i| i.to_str())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("{} isn't a valid FAT file name", path.display())))?;
+
+        // FAT has no symlinks, so they're replaced by whatever they point to
+        if fs::metadata(&path)?.is_dir() {
+            copy_dir(&dir.create_dir(name)?, &path)?;
+        } else {
+            let mut file = dir.create_file(name)?;
+            file.truncate()?;
+            io::copy(&mut fs::File::open(&path)?, &mut file)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Read;
+
+    use fatfs::FileSystem;
+    use fatfs::FsOptions;
+
+    use crate::build::*;
+    use crate::build::testing::*;
+
+    #[test]
+    pub fn copies_staged_files() {
+        let dir = TempDir::new("fat");
+        let staging = dir.join("staging");
+        fs::create_dir_all(staging.join("EFI/BOOT")).unwrap();
+        fs::write(staging.join("EFI/BOOT/BOOTX64.EFI"), b"bootloader").unwrap();
+        fs::write(staging.join("A Rather Long File Name.txt"), b"hello").unwrap();
+
+        let image = dir.join("image.img");
+        fs::File::create(&image).unwrap().set_len(40 << 20).unwrap();
+        let extent = Extent { offset: 1 << 20, size: 36 << 20 };
+        write_fat32(Region::open(&image, extent).unwrap(), "efi", &staging).unwrap();
+
+        let filesystem = FileSystem::new(Region::open(&image, extent).unwrap(), FsOptions::new()).unwrap();
+        assert_eq!(filesystem.fat_type(), fatfs::FatType::Fat32);
+        assert_eq!(filesystem.volume_label(), "EFI");
+
+        let read = |path: &str| {
+            let mut content = String::new();
+            filesystem.root_dir().open_file(path).unwrap().read_to_string(&mut content).unwrap();
+            content
+        };
+        assert_eq!(read("EFI/BOOT/BOOTX64.EFI"), "bootloader");
+        assert_eq!(read("A Rather Long File Name.txt"), "hello");
+
+        let names = filesystem.root_dir().iter()
+            .map(|i| i.unwrap().file_name())
+            .collect::<Vec<_>>();
+        assert!(names.contains(&"A Rather Long File Name.txt".to_owned()), "{:?}", names);
+    }
+}
@@ -0,0 +1,299 @@
+use std::fs;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+
+use crate::build::*;
+use crate::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Ron,
+    Json,
+    Json5,
+    Toml
+}
+
+impl ConfigFormat {
+    pub const ALL: [ConfigFormat; 4] = [ConfigFormat::Ron, ConfigFormat::Json, ConfigFormat::Json5, ConfigFormat::Toml];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConfigFormat::Ron => "RON",
+            ConfigFormat::Json => "JSON",
+            ConfigFormat::Json5 => "JSON5",
+            ConfigFormat::Toml => "TOML"
+        }
+    }
+
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            ConfigFormat::Ron => &["ron"],
+            ConfigFormat::Json => &["json"],
+            ConfigFormat::Json5 => &["json5"],
+            ConfigFormat::Toml => &["toml"]
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        Self::ALL.into_iter()
+            .find(|i| i.extensions().contains(&ext.as_str()))
+    }
+
+    /// Guesses the format from the first meaningful character of the file. This can't tell JSON from JSON5, and is
+    /// only meant to decide which parser gets the first go.
+    pub fn sniff(content: &str) -> Self {
+        let first = content.lines()
+            .map(str::trim)
+            .find(|i| !i.is_empty() && !i.starts_with("//") && !i.starts_with('#'));
+
+        match first {
+            Some(line) if line.starts_with('{') => ConfigFormat::Json,
+            Some(line) if line.starts_with('[') || line.contains('=') => ConfigFormat::Toml,
+            _ => ConfigFormat::Ron
+        }
+    }
+
+    /// Parses `content` as a `BuildConfig`, falling back to the older layout some configs are still written in. If
+    /// neither fits, the error is the one from the current layout.
+    pub fn parse(&self, content: &str) -> Result<BuildConfig, Error> {
+        self.parse_as::<BuildConfig>(content)
+            .or_else(|err| self.parse_as::<super::legacy::BuildConfig>(content).map(BuildConfig::from).map_err(|_| err))
+    }
+
+    fn parse_as<T: DeserializeOwned>(&self, content: &str) -> Result<T, Error> {
+        Ok(match self {
+            ConfigFormat::Ron => ron::from_str(content)?,
+            ConfigFormat::Json => serde_json::from_str(content)?,
+            ConfigFormat::Json5 => json5::from_str(content)?,
+            ConfigFormat::Toml => toml::from_str(content)?
+        })
+    }
+
+    /// RON output uses the same four-space indentation as the configs in the repository.
+    pub fn serialize(&self, config: &BuildConfig) -> Result<String, Error> {
+        Ok(match self {
+            ConfigFormat::Ron => ron::ser::to_string_pretty(config, ron::ser::PrettyConfig::new()
+                .indentor("    ".to_owned()))?,
+            ConfigFormat::Json => serde_json::to_string_pretty(config)?,
+            ConfigFormat::Json5 => json5::to_string(config)?,
+            ConfigFormat::Toml => toml::to_string_pretty(config)?
+        })
+    }
+
+    /// Parses `content` using the format implied by `path`. Files without a known extension are tried with every
+    /// format, starting with the sniffed one, whose error is reported if none of them succeed.
+    pub fn detect(path: &Path, content: &str) -> Result<(BuildConfig, Self), Error> {
+        if let Some(format) = Self::from_path(path) {
+            return format.parse(content).map(|config| (config, format));
+        }
+
+        let sniffed = Self::sniff(content);
+        let mut first_err = None;
+
+        for format in std::iter::once(sniffed).chain(Self::ALL.into_iter().filter(|i| *i != sniffed)) {
+            match format.parse(content) {
+                Ok(config) => return Ok((config, format)),
+                Err(err) => { first_err.get_or_insert(err); }
+            }
+        }
+
+        Err(first_err.expect("at least one format was tried"))
+    }
+}
+
+impl std::str::FromStr for ConfigFormat {
+    type Err = String;
+
+    /// Accepts either the name or an extension of the format, in any case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        Self::ALL.into_iter()
+            .find(|i| i.name().to_lowercase() == s || i.extensions().contains(&s.as_str()))
+            .ok_or_else(|| format!("unknown format '{}', expected one of ron, json, json5 or toml", s))
+    }
+}
+
+/// Reads a `BuildConfig` from `path`, returning the format it was stored in.
+pub fn load(path: &Path) -> Result<(BuildConfig, ConfigFormat), Error> {
+    let content = fs::read_to_string(path)?;
+    ConfigFormat::detect(path, &content)
+}
+
+/// Writes `config` to `path` in the given format.
+pub fn save(path: &Path, config: &BuildConfig, format: ConfigFormat) -> Result<(), Error> {
+    fs::write(path, format.serialize(config)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::build::*;
+
+    const RON: &str = r#"(
+        name: "Test Config",
+        partitions: [(label: "swap", purpose: Swap, offset: Some(34), size: None)],
+        image_size: Some(268435456),
+    )"#;
+
+    const JSON: &str = r#"{
+        "name": "Test Config",
+        "partitions": [{ "label": "swap", "purpose": "Swap", "offset": 34, "size": null }],
+        "image_size": 268435456
+    }"#;
+
+    const JSON5: &str = r#"{
+        // JSON5 allows comments, unquoted keys and trailing commas
+        name: 'Test Config',
+        partitions: [{ label: 'swap', purpose: 'Swap', offset: 34, size: null }],
+        image_size: 268435456,
+    }"#;
+
+    const TOML: &str = r#"
+        name = "Test Config"
+        image_size = 268435456
+
+        [[partitions]]
+        label = "swap"
+        purpose = "Swap"
+        offset = 34
+    "#;
+
+    #[test]
+    pub fn parses_every_format() {
+        let expected = BuildConfig {
+            name: "Test Config".to_owned(),
+            partitions: vec![Partition {
+                label: "swap".to_owned(),
+                purpose: PartitionPreset::Swap,
+                offset: Some(34),
+                size: None
+            }],
+            image_size: Some(268435456)
+        };
+
+        for (content, ext, format) in [(RON, "ron", ConfigFormat::Ron), (JSON, "json", ConfigFormat::Json), (JSON5, "json5", ConfigFormat::Json5), (TOML, "toml", ConfigFormat::Toml)] {
+            let path = format!("config.{}", ext);
+            assert_eq!(ConfigFormat::detect(Path::new(&path), content).unwrap(), (expected.clone(), format));
+
+            let (config, _) = ConfigFormat::detect(Path::new("config"), content).unwrap();
+            assert_eq!(config, expected);
+        }
+    }
+
+    #[test]
+    pub fn reads_old_configs() {
+        let ron = r#"(
+            name: "Test Config",
+            partitions: [(label: "firmware", purpose: Raw, offset: None, size: Some(1024))],
+            image_size: None,
+        )"#;
+        let json = r#"{
+            "name": "Test Config",
+            "partitions": [{ "label": "firmware", "purpose": "Raw", "offset": null, "size": 1024 }],
+            "image_size": null
+        }"#;
+
+        for (content, format) in [(ron, ConfigFormat::Ron), (json, ConfigFormat::Json)] {
+            let config = format.parse(content).unwrap();
+            assert_eq!(config.partitions[0].purpose, PartitionPreset::Raw { source: None, fill: None });
+        }
+
+        let nodes_ron = r#"(
+            name: "Test Config",
+            partitions: [
+                (label: "root", purpose: RedoxFS(filesystem: [
+                    (path: "/etc/hostname", source: Static("hostname"), dependencies: None),
+                    (path: "/bin/init", source: Prebuilt("file:///opt/redox/init"), dependencies: None),
+                    (path: "/bin/ion", source: Cargo(repo: "https://gitlab.redox-os.org/redox-os/ion.git", origin: None), dependencies: None),
+                ], post_run: None), offset: None, size: None),
+                (label: "firmware", purpose: Raw, offset: None, size: Some(1024)),
+            ],
+            image_size: None,
+        )"#;
+        let nodes_json = r#"{
+            "name": "Test Config",
+            "partitions": [{ "label": "root", "purpose": { "RedoxFS": { "filesystem": [
+                { "path": "/etc/hostname", "source": { "Static": "hostname" }, "dependencies": null },
+                { "path": "/bin/init", "source": { "Prebuilt": "file:///opt/redox/init" }, "dependencies": null },
+                { "path": "/bin/ion", "source": { "Cargo": { "repo": "https://gitlab.redox-os.org/redox-os/ion.git", "origin": null } }, "dependencies": null }
+            ], "post_run": null } }, "offset": null, "size": null }, { "label": "firmware", "purpose": "Raw", "offset": null, "size": 1024 }],
+            "image_size": null
+        }"#;
+
+        for (content, format) in [(nodes_ron, ConfigFormat::Ron), (nodes_json, ConfigFormat::Json)] {
+            let config = format.parse(content).unwrap();
+            let sources = config.partitions[0].purpose.filesystem()
+                .iter()
+                .map(|i| i.source.clone())
+                .collect::<Vec<_>>();
+            assert_eq!(sources, [
+                NodeSource::Static { location: "hostname".to_owned(), sha256: None },
+                NodeSource::Prebuilt { location: "file:///opt/redox/init".to_owned(), sha256: None },
+                NodeSource::Cargo { repo: "https://gitlab.redox-os.org/redox-os/ion.git".to_owned(), origin: None, target: None, features: None }
+            ]);
+            assert_eq!(config.partitions[1].purpose, PartitionPreset::Raw { source: None, fill: None });
+        }
+
+        // A config which is broken either way reports what's wrong with it as a current config
+        let broken = ron.replace("Raw", "Raw(fill: Some(\"ff\"))");
+        let err = ConfigFormat::Ron.parse(&broken).unwrap_err().to_string();
+        assert!(err.to_lowercase().contains("integer"), "{}", err);
+    }
+
+    #[test]
+    pub fn round_trips_every_format() {
+        let config = BuildConfig {
+            name: "Test Config".to_owned(),
+            partitions: vec![Partition {
+                label: "boot".to_owned(),
+                purpose: PartitionPreset::Boot {
+                    bios: Some("bootloader.bios".into()),
+                    uefi: None,
+                    post_run: Command {
+                        cmd: vec!["/usr/bin/nu".to_owned(), "-c".to_owned(), "echo hello".to_owned()],
+                        cwd: None,
+                        env: Some([("A".to_owned(), "B".to_owned())].into_iter().collect())
+                    }
+                },
+                offset: Some(34),
+                size: None
+            }, Partition {
+                label: "root".to_owned(),
+                purpose: PartitionPreset::RedoxFS {
+                    filesystem: vec![FSNode {
+                        path: "/bin/ion".into(),
+                        source: NodeSource::Cargo { repo: "https://gitlab.redox-os.org/redox-os/ion.git".to_owned(), origin: Some(GitOrigin::Branch("master".to_owned())), target: Some("x86_64-unknown-redox".to_owned()), features: None },
+                        dependencies: None
+                    }, FSNode {
+                        path: "/bin/init".into(),
+                        source: NodeSource::Prebuilt { location: "file:///opt/redox/init".to_owned(), sha256: Some("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".to_owned()) },
+                        dependencies: None
+                    }],
+                    post_run: None
+                },
+                offset: None,
+                size: Some(1024)
+            }, Partition {
+                label: "swap".to_owned(),
+                purpose: PartitionPreset::Swap,
+                offset: None,
+                size: None
+            }, Partition {
+                label: "firmware".to_owned(),
+                purpose: PartitionPreset::Raw { source: Some("firmware.bin".into()), fill: Some(0xFF) },
+                offset: None,
+                size: Some(1024)
+            }],
+            image_size: None
+        };
+
+        for format in ConfigFormat::ALL {
+            let content = format.serialize(&config).unwrap();
+            assert_eq!(format.parse(&content).unwrap(), config, "{:?} didn't round-trip:\n{}", format, content);
+        }
+    }
+}
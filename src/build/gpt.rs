@@ -0,0 +1,199 @@
+use std::fs::File;
+use std::io;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+
+use uuid::uuid;
+use uuid::Uuid;
+
+use crate::build::*;
+
+pub const SECTOR_SIZE: u64 = 512;
+/// How many partition entries the tables have room for. 128 is the minimum the spec allows, and what every tool uses.
+pub const ENTRY_COUNT: usize = 128;
+const ENTRY_SIZE: usize = 128;
+const HEADER_SIZE: usize = 92;
+pub const TABLE_SECTORS: u64 = (ENTRY_COUNT * ENTRY_SIZE) as u64 / SECTOR_SIZE;
+/// The protective MBR, the primary header and its partition entries come before the first partition.
+pub const FIRST_USABLE: u64 = (2 + TABLE_SECTORS) * SECTOR_SIZE;
+/// The backup partition entries and header take up the end of the image.
+pub const TABLE_TAIL: u64 = (TABLE_SECTORS + 1) * SECTOR_SIZE;
+
+impl PartitionPreset {
+    pub fn type_guid(&self) -> Uuid {
+        match self {
+            // Linux filesystem data
+            PartitionPreset::Ext4 { .. } | PartitionPreset::RedoxFS { .. } => uuid!("0FC63DAF-8483-4772-8E79-3D69D8477DE4"),
+            // Microsoft basic data
+            PartitionPreset::Fat32 { .. } => uuid!("EBD0A0A2-B9E5-4433-87C0-68B6B72699C7"),
+            // EFI system partition
+            PartitionPreset::Boot { uefi: Some(_), .. } => uuid!("C12A7328-F81F-11D2-BA4B-00A0C93EC93B"),
+            // BIOS boot partition
+            PartitionPreset::Boot { uefi: None, .. } => uuid!("21686148-6449-6E6F-744E-656564454649"),
+            // Linux swap
+            PartitionPreset::Swap => uuid!("0657FD6D-A4AB-43C4-84E5-0933C84B4F4F"),
+            // Linux reserved
+            PartitionPreset::Raw { .. } => uuid!("8DA63339-0007-60C0-C436-083AC8230908")
+        }
+    }
+}
+
+/// GUIDs are derived from the config and partition names rather than generated randomly, so that building the same
+/// config twice gives the same image.
+fn guid(config: &BuildConfig, partition: Option<&Partition>) -> Uuid {
+    let name = match partition {
+        Some(partition) => format!("{}/{}", config.name, partition.label),
+        None => config.name.clone()
+    };
+
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, name.as_bytes())
+}
+
+/// Truncates `file` to the size of the image, leaving it sparse, and writes a protective MBR followed by the primary and
+/// backup GPT.
+pub fn write_gpt(file: &mut File, config: &BuildConfig, layout: &Layout) -> io::Result<()> {
+    let sectors = layout.image_size / SECTOR_SIZE;
+    let last_lba = sectors - 1;
+
+    let mut entries = vec![0u8; ENTRY_COUNT * ENTRY_SIZE];
+    for ((partition, extent), entry) in config.partitions.iter().zip(&layout.partitions).zip(entries.chunks_mut(ENTRY_SIZE)) {
+        entry[0..16].copy_from_slice(&partition.purpose.type_guid().to_bytes_le());
+        entry[16..32].copy_from_slice(&guid(config, Some(partition)).to_bytes_le());
+        entry[32..40].copy_from_slice(&extent.first_lba().to_le_bytes());
+        entry[40..48].copy_from_slice(&extent.last_lba().to_le_bytes());
+
+        for (unit, bytes) in partition.label.encode_utf16().take(36).zip(entry[56..128].chunks_mut(2)) {
+            bytes.copy_from_slice(&unit.to_le_bytes());
+        }
+    }
+
+    let entries_crc = crc32fast::hash(&entries);
+    let header = |current: u64, backup: u64, entries_lba: u64| {
+        let mut header = [0u8; SECTOR_SIZE as usize];
+        header[0..8].copy_from_slice(b"EFI PART");
+        header[8..12].copy_from_slice(&0x00010000u32.to_le_bytes());
+        header[12..16].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        header[24..32].copy_from_slice(&current.to_le_bytes());
+        header[32..40].copy_from_slice(&backup.to_le_bytes());
+        header[40..48].copy_from_slice(&(FIRST_USABLE / SECTOR_SIZE).to_le_bytes());
+        header[48..56].copy_from_slice(&(last_lba - TABLE_SECTORS - 1).to_le_bytes());
+        header[56..72].copy_from_slice(&guid(config, None).to_bytes_le());
+        header[72..80].copy_from_slice(&entries_lba.to_le_bytes());
+        header[80..84].copy_from_slice(&(ENTRY_COUNT as u32).to_le_bytes());
+        header[84..88].copy_from_slice(&(ENTRY_SIZE as u32).to_le_bytes());
+        header[88..92].copy_from_slice(&entries_crc.to_le_bytes());
+
+        let crc = crc32fast::hash(&header[..HEADER_SIZE]);
+        header[16..20].copy_from_slice(&crc.to_le_bytes());
+        header
+    };
+
+    // A single partition of type 0xEE covering the whole disk (or as much of it as fits), so that tools which only
+    // know MBR leave it alone
+    let mut mbr = [0u8; SECTOR_SIZE as usize];
+    mbr[446..462].copy_from_slice(&[
+        0x00,
+        0x00, 0x02, 0x00,
+        0xEE,
+        0xFF, 0xFF, 0xFF,
+        0x01, 0x00, 0x00, 0x00,
+        0, 0, 0, 0
+    ]);
+    mbr[458..462].copy_from_slice(&(last_lba.min(u32::MAX as u64) as u32).to_le_bytes());
+    mbr[510..512].copy_from_slice(&[0x55, 0xAA]);
+
+    file.set_len(0)?;
+    file.set_len(layout.image_size)?;
+
+    let backup_entries = last_lba - TABLE_SECTORS;
+    for (lba, data) in [
+        (0, &mbr[..]),
+        (1, &header(1, last_lba, 2)[..]),
+        (2, &entries[..]),
+        (backup_entries, &entries[..]),
+        (last_lba, &header(last_lba, 1, backup_entries)[..])
+    ] {
+        file.seek(SeekFrom::Start(lba * SECTOR_SIZE))?;
+        file.write_all(data)?;
+    }
+
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::fs::File;
+
+    use crate::build::*;
+    use crate::build::testing::*;
+
+    fn u64_at(data: &[u8], at: usize) -> u64 {
+        u64::from_le_bytes(data[at..at + 8].try_into().unwrap())
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    fn table_config() -> BuildConfig {
+        config(vec![
+            partition("boot", PartitionPreset::Fat32 { filesystem: vec![], post_run: None }, Some(1 << 20), Some(1 << 20)),
+            partition("swap", PartitionPreset::Swap, None, None)
+        ], Some(8 << 20))
+    }
+
+    #[test]
+    pub fn writes_valid_tables() {
+        let config = table_config();
+        let layout = Layout::new(&config).unwrap();
+
+        let dir = TempDir::new("gpt");
+        let path = dir.join("image.img");
+        write_gpt(&mut File::create(&path).unwrap(), &config, &layout).unwrap();
+        let image = fs::read(&path).unwrap();
+
+        assert_eq!(image.len() as u64, 8 << 20);
+        assert_eq!(image[450], 0xEE);
+        assert_eq!(&image[510..512], &[0x55, 0xAA]);
+
+        let last_lba = (8 << 20) / SECTOR_SIZE - 1;
+        for (header_lba, entries_lba) in [(1, 2), (last_lba, last_lba - TABLE_SECTORS)] {
+            let at = (header_lba * SECTOR_SIZE) as usize;
+            let mut header = image[at..at + 92].to_vec();
+            assert_eq!(&header[0..8], b"EFI PART");
+            assert_eq!(u64_at(&header, 24), header_lba);
+            assert_eq!(u64_at(&header, 72), entries_lba);
+
+            let crc = u32_at(&header, 16);
+            header[16..20].fill(0);
+            assert_eq!(crc32fast::hash(&header), crc);
+
+            let at = (entries_lba * SECTOR_SIZE) as usize;
+            let entries = &image[at..at + 128 * 128];
+            assert_eq!(crc32fast::hash(entries), u32_at(&header, 88));
+
+            assert_eq!(u64_at(entries, 32), 2048);
+            assert_eq!(u64_at(entries, 40), 4095);
+            assert_eq!(&entries[56..64], &[b'b', 0, b'o', 0, b'o', 0, b't', 0]);
+            assert_eq!(u64_at(entries, 128 + 32), 4096);
+            assert_eq!(u64_at(entries, 128 + 40), last_lba - TABLE_SECTORS - 1);
+        }
+    }
+
+    /// Cross-checks the tables with `sgdisk`, which isn't installed everywhere. Run with `cargo test -- --ignored`.
+    #[test]
+    #[ignore = "needs sgdisk"]
+    pub fn sgdisk_verifies_tables() {
+        let config = table_config();
+        let dir = TempDir::new("sgdisk");
+        let path = dir.join("image.img");
+        write_gpt(&mut File::create(&path).unwrap(), &config, &Layout::new(&config).unwrap()).unwrap();
+
+        let output = std::process::Command::new("sgdisk").arg("--verify").arg(&path).output()
+            .expect("this test needs sgdisk from gdisk");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("No problems found"), "sgdisk found problems:\n{}", stdout);
+    }
+}
@@ -1,3 +1,273 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+
+use vizia::prelude::*;
+
+use crate::build::*;
+use crate::Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobKind {
+    Node {
+        partition: usize,
+        node: FSNode
+    },
+    /// Creates the image file and writes its partition tables.
+    Table,
+    Partition {
+        index: usize,
+        partition: Partition
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Data)]
+pub enum JobStatus {
+    #[default]
+    Pending,
+    Running,
+    Succeeded,
+    /// The output of an earlier build was reused.
+    Cached,
+    Failed,
+    Cancelled
+}
+
+#[derive(Debug, Clone)]
 pub struct Job {
+    pub id: usize,
+    pub name: String,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    pub started: Option<Instant>,
+    pub finished: Option<Instant>,
+    pub error: Option<Arc<Error>>,
+    /// Everything the job's commands have printed so far.
+    pub log: Vec<String>
+}
+
+/// Logs only ever grow, so comparing their lengths is enough to notice new output without comparing every line.
+impl PartialEq for Job {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+            && self.name == other.name
+            && self.kind == other.kind
+            && self.status == other.status
+            && self.started == other.started
+            && self.finished == other.finished
+            && self.error.is_some() == other.error.is_some()
+            && self.log.len() == other.log.len()
+    }
+}
+
+impl Data for Job {
+    fn same(&self, other: &Self) -> bool {
+        *self == *other
+    }
+}
+
+/// Paths and state shared by every job of a single build.
+#[derive(Debug, Clone)]
+pub struct BuildContext {
+    pub out_dir: PathBuf,
+    pub cache_dir: PathBuf,
+    pub cache_limit: Option<u64>,
+    pub image: PathBuf,
+    pub config: BuildConfig,
+    pub layout: Layout,
+    pub handle: BuildHandle
+}
+
+impl BuildContext {
+    pub fn new(config: BuildConfig, layout: Layout, options: BuildOptions, handle: BuildHandle) -> Self {
+        Self {
+            image: options.out_dir.join(format!("{}.img", slug(&config.name))),
+            out_dir: options.out_dir,
+            cache_dir: options.cache_dir,
+            cache_limit: options.cache_limit,
+            config,
+            layout,
+            handle
+        }
+    }
+
+    /// The directory a partition's filesystem is assembled in before it's written into the image. It's named after the
+    /// partition's index as well as its label, since labels such as "Boot" and "boot" make the same slug.
+    pub fn staging(&self, partition: usize) -> PathBuf {
+        let name = match self.config.partitions.get(partition) {
+            Some(i) => format!("{}-{}", partition, slug(&i.label)),
+            None => partition.to_string()
+        };
+
+        self.staging_root().join(name)
+    }
+
+    /// Holds the staging directory of every partition.
+    pub fn staging_root(&self) -> PathBuf {
+        self.out_dir.join("staging")
+    }
+
+    /// Where `node` ends up inside the partition's staging directory.
+    pub fn staged_path(&self, partition: usize, node: &Path) -> PathBuf {
+        self.staging(partition).join(node.strip_prefix("/").unwrap_or(node))
+    }
+}
+
+impl Job {
+    pub fn new(id: usize, kind: JobKind, config: &BuildConfig) -> Self {
+        let name = match &kind {
+            JobKind::Node { partition, node } => format!("{}:{}", config.partitions[*partition].label, node.path.display()),
+            JobKind::Table => "Partition table".to_owned(),
+            JobKind::Partition { partition, .. } => format!("{} {}", partition.purpose.kind().name(), partition.label)
+        };
+
+        Self {
+            id,
+            name,
+            kind,
+            status: JobStatus::Pending,
+            started: None,
+            finished: None,
+            error: None,
+            log: vec![]
+        }
+    }
+
+    /// Runs the job, passing every line of output from the commands it runs to `log`. Returns `JobStatus::Cached` if the
+    /// job's output could be taken from the cache instead, and `JobStatus::Succeeded` otherwise.
+    pub fn run(&self, cx: &BuildContext, log: &dyn Fn(&str)) -> Result<JobStatus, Error> {
+        match &self.kind {
+            JobKind::Node { partition, node } => {
+                let dest = cx.staged_path(*partition, &node.path);
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+
+                let key = node_key(cx, node, log)?;
+                if let Some(key) = &key {
+                    if restore_node(cx, key, &dest)? {
+                        return Ok(JobStatus::Cached);
+                    }
+                }
+
+                match &node.source {
+                    NodeSource::Static { location, sha256 } | NodeSource::Prebuilt { location, sha256 } => {
+                        install_artifact(location, sha256.as_deref(), &dest)?;
+                    },
+                    NodeSource::Custom(cmd) => {
+                        let Some(mut command) = cmd.process() else {
+                            return Err(Error::from(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{}: empty command", self.name))));
+                        };
+
+                        command.env("GUIBUILD_OUTPUT", &dest);
+                        run_command(command, &cx.handle, log)?;
+                    },
+                    NodeSource::Cargo { .. } => build_cargo(cx, &node.source, &dest, log)?,
+                };
+
+                if let Some(key) = &key {
+                    store_node(cx, key, &dest)?;
+                }
+                Ok(JobStatus::Succeeded)
+            },
+            JobKind::Table => {
+                let mut file = fs::File::create(&cx.image)?;
+                write_gpt(&mut file, &cx.config, &cx.layout)?;
+                Ok(JobStatus::Succeeded)
+            },
+            JobKind::Partition { index, partition } => {
+                let staging = cx.staging(*index);
+                fs::create_dir_all(&staging)?;
+
+                let extent = cx.layout.partitions[*index];
+                if let Some(mut command) = partition.purpose.post_run().and_then(Command::process) {
+                    command.envs(post_run_env(cx, partition, extent, &staging)?);
+                    run_command(command, &cx.handle, log)?;
+                }
+
+                match &partition.purpose {
+                    PartitionPreset::Fat32 { .. } => write_fat32(Region::open(&cx.image, extent)?, &partition.label, &staging)?,
+                    PartitionPreset::Ext4 { .. } => write_ext4(&cx.image, extent, &partition.label, &staging)?,
+                    PartitionPreset::RedoxFS { .. } => write_redoxfs(Region::open(&cx.image, extent)?, &staging)?,
+                    PartitionPreset::Swap => write_swap(&mut Region::open(&cx.image, extent)?, &cx.config, partition)?,
+                    PartitionPreset::Raw { source, fill } => write_raw(&mut Region::open(&cx.image, extent)?, source.as_deref(), fill.unwrap_or(0))?,
+                    PartitionPreset::Boot { bios, uefi, .. } => {
+                        if let Some(bios) = bios {
+                            install_bios(&cx.image, &cx.layout, bios)?;
+                        }
+
+                        if let Some(uefi) = uefi {
+                            install_uefi(Region::open(&cx.image, extent)?, &partition.label, &staging, uefi)?;
+                        }
+                    }
+                }
+
+                Ok(JobStatus::Succeeded)
+            }
+        }
+    }
+}
+
+/// What a partition's `post_run` hook is told about it. Paths are made absolute, since the hook can run somewhere else.
+fn post_run_env(cx: &BuildContext, partition: &Partition, extent: Extent, staging: &Path) -> std::io::Result<Vec<(&'static str, OsString)>> {
+    Ok(vec![
+        ("GUIBUILD_STAGING", fs::canonicalize(staging)?.into_os_string()),
+        ("GUIBUILD_LABEL", OsString::from(&partition.label)),
+        ("GUIBUILD_OFFSET", OsString::from(extent.offset.to_string())),
+        ("GUIBUILD_SIZE", OsString::from(extent.size.to_string())),
+        ("GUIBUILD_IMAGE", fs::canonicalize(&cx.image)?.into_os_string())
+    ])
+}
+
+/// Turns a human-readable name into something safe to use as a file name.
+pub fn slug(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c.to_ascii_lowercase() } else { '-' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use fatfs::FileSystem;
+    use fatfs::FsOptions;
+
+    use crate::build::*;
+    use crate::build::testing::*;
+
+    #[test]
+    pub fn stages_partitions_separately() {
+        let swap = |label| partition(label, PartitionPreset::Swap, None, Some(1 << 20));
+        let config = config(vec![swap("Boot"), swap("boot")], None);
+        let options = BuildOptions { out_dir: "out".into(), cache_dir: "cache".into(), cache_limit: None, jobs: 1 };
+        let cx = BuildContext::new(config.clone(), Layout::new(&config).unwrap(), options, BuildHandle::default());
+
+        assert_ne!(cx.staging(0), cx.staging(1));
+    }
+
+    #[test]
+    pub fn runs_post_run_before_sealing() {
+        let dir = TempDir::new("post-run");
+        let script = "echo \"$GUIBUILD_LABEL $GUIBUILD_OFFSET $GUIBUILD_SIZE $(basename \"$GUIBUILD_IMAGE\")\" > \"$GUIBUILD_STAGING/hook.txt\"";
+        let efi = PartitionPreset::Fat32 {
+            filesystem: vec![],
+            post_run: Some(Command { cmd: vec!["sh".to_owned(), "-c".to_owned(), script.to_owned()], cwd: None, env: None })
+        };
+        let config = config(vec![partition("efi", efi, None, Some(36 << 20))], None);
+        let options = BuildOptions { out_dir: dir.to_path_buf(), cache_dir: dir.join("cache"), cache_limit: None, jobs: 1 };
+        let cx = BuildContext::new(config.clone(), Layout::new(&config).unwrap(), options, BuildHandle::default());
+
+        for job in jobs(&config) {
+            job.run(&cx, &|_| {}).unwrap();
+        }
 
-}
\ No newline at end of file
+        let filesystem = FileSystem::new(Region::open(&cx.image, cx.layout.partitions[0]).unwrap(), FsOptions::new()).unwrap();
+        let mut content = String::new();
+        filesystem.root_dir().open_file("hook.txt").unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, format!("efi {} {} test-config.img\n", 1 << 20, 36 << 20));
+    }
+}
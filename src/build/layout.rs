@@ -0,0 +1,266 @@
+use std::fmt;
+
+use vizia::prelude::*;
+
+use crate::build::*;
+
+/// Partitions which aren't given an offset start on a multiple of this, as do the ones after a partition without a
+/// size. Explicit offsets only have to be a multiple of `SECTOR_SIZE`.
+pub const ALIGNMENT: u64 = 1 << 20;
+
+#[derive(Debug)]
+pub enum LayoutError {
+    /// A partition's offset isn't a multiple of `SECTOR_SIZE`.
+    Misaligned { label: String, offset: u64 },
+    /// A partition has no size, and there is nothing to work one out from.
+    Unsized(String),
+    /// Two partitions without a size are next to each other, so there's no telling how to split the space between them.
+    Ambiguous(String, String),
+    /// A partition without a size was placed where the partitions after it leave no space for it.
+    NoSpace { label: String, needed: u64, available: u64 },
+    /// A partition starts inside the primary partition table.
+    OutOfBounds { label: String, start: u64 },
+    /// The partitions, together with the backup partition table, don't fit in `BuildConfig::image_size`.
+    TooSmall { needed: u64, image_size: u64 },
+    Overlap(String, String),
+    TooManyPartitions(usize)
+}
+
+impl LayoutError {
+    /// The partition the error is about, if it's about a single one.
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            LayoutError::Misaligned { label, .. }
+                | LayoutError::Unsized(label)
+                | LayoutError::Ambiguous(label, _)
+                | LayoutError::NoSpace { label, .. }
+                | LayoutError::OutOfBounds { label, .. }
+                | LayoutError::Overlap(_, label) => Some(label),
+            LayoutError::TooSmall { .. } | LayoutError::TooManyPartitions(_) => None
+        }
+    }
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::Misaligned { label, offset } => write!(f, "partition '{}' starts at byte {}, which isn't a multiple of the {} byte sector size", label, offset, SECTOR_SIZE),
+            LayoutError::Unsized(label) => write!(f, "partition '{}' has no size, and the image has no size for it to fill", label),
+            LayoutError::Ambiguous(a, b) => write!(f, "partitions '{}' and '{}' both have no size, so there's no telling how to split the space between them", a, b),
+            LayoutError::NoSpace { label, needed, available } => write!(f, "partition '{}' has no size, but the partitions after it need {} of the {} left", label, format_size(*needed), format_size(*available)),
+            LayoutError::OutOfBounds { label, start } => write!(f, "partition '{}' starts at byte {}, inside the partition table (the first usable byte is {})", label, start, FIRST_USABLE),
+            LayoutError::TooSmall { needed, image_size } => write!(f, "the partitions need an image of at least {}, but it's only {}", format_size(*needed), format_size(*image_size)),
+            LayoutError::Overlap(a, b) => write!(f, "partitions '{}' and '{}' overlap", a, b),
+            LayoutError::TooManyPartitions(count) => write!(f, "the image has {} partitions, but the partition table only has room for {}", count, ENTRY_COUNT)
+        }
+    }
+}
+
+/// A byte range of the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Extent {
+    pub offset: u64,
+    pub size: u64
+}
+
+impl Extent {
+    pub fn end(&self) -> u64 {
+        self.offset + self.size
+    }
+
+    pub fn first_lba(&self) -> u64 {
+        self.offset / SECTOR_SIZE
+    }
+
+    /// GPT entries store the last sector of a partition, not the one after it.
+    pub fn last_lba(&self) -> u64 {
+        self.end() / SECTOR_SIZE - 1
+    }
+}
+
+/// Where every partition of a config ends up in the image, in the same order as `BuildConfig::partitions`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub image_size: u64,
+    /// The smallest image the partitions fit in, including the partition tables.
+    pub minimum_size: u64,
+    pub partitions: Vec<Extent>
+}
+
+impl Data for Layout {
+    fn same(&self, other: &Self) -> bool {
+        *self == *other
+    }
+}
+
+fn align_up(value: u64, alignment: u64) -> u64 {
+    value.div_ceil(alignment) * alignment
+}
+
+fn align_down(value: u64, alignment: u64) -> u64 {
+    value / alignment * alignment
+}
+
+impl Layout {
+    /// Partitions without an offset are placed at the first `ALIGNMENT` boundary after the one before them. A partition
+    /// without a size gets whatever space is left up to the next partition with an offset (or the end of the image)
+    /// once the partitions in between are placed. Without an `image_size`, the image is the smallest multiple of
+    /// `ALIGNMENT` everything fits in.
+    pub fn new(config: &BuildConfig) -> Result<Self, LayoutError> {
+        if config.partitions.len() > ENTRY_COUNT {
+            return Err(LayoutError::TooManyPartitions(config.partitions.len()));
+        }
+
+        let usable_end = config.image_size.map(|size| align_down(size, SECTOR_SIZE).saturating_sub(TABLE_TAIL));
+        let sized = |partition: &Partition| partition.size.map(|size| align_up(size, SECTOR_SIZE));
+
+        let mut partitions = Vec::with_capacity(config.partitions.len());
+        let mut cursor = FIRST_USABLE;
+        for (index, partition) in config.partitions.iter().enumerate() {
+            let offset = match partition.offset {
+                Some(offset) if offset % SECTOR_SIZE != 0 => return Err(LayoutError::Misaligned { label: partition.label.clone(), offset }),
+                Some(offset) => offset,
+                None => align_up(cursor, ALIGNMENT)
+            };
+
+            let size = match sized(partition) {
+                Some(size) => size,
+                None => {
+                    let rest = &config.partitions[index + 1..];
+                    let boundary = rest.iter()
+                        .find_map(|i| i.offset)
+                        .or(usable_end)
+                        .ok_or_else(|| LayoutError::Unsized(partition.label.clone()))?;
+
+                    // Place the partitions up to the boundary backwards from it, to find where this one has to end
+                    let following = rest.iter()
+                        .take_while(|i| i.offset.is_none())
+                        .collect::<Vec<_>>();
+
+                    let mut end = boundary;
+                    for other in following.iter().rev() {
+                        let size = sized(other).ok_or_else(|| LayoutError::Ambiguous(partition.label.clone(), other.label.clone()))?;
+                        end = align_down(end.saturating_sub(size), ALIGNMENT);
+                    }
+
+                    if end <= offset {
+                        return Err(LayoutError::NoSpace {
+                            label: partition.label.clone(),
+                            needed: following.iter().filter_map(|i| sized(i)).sum(),
+                            available: boundary.saturating_sub(offset)
+                        });
+                    }
+
+                    end - offset
+                }
+            };
+
+            let extent = Extent { offset, size };
+            if extent.offset < FIRST_USABLE {
+                return Err(LayoutError::OutOfBounds { label: partition.label.clone(), start: extent.offset });
+            }
+
+            cursor = extent.end();
+            partitions.push(extent);
+        }
+
+        let minimum_size = partitions.iter()
+            .map(Extent::end)
+            .max()
+            .unwrap_or(FIRST_USABLE) + TABLE_TAIL;
+
+        let image_size = match config.image_size {
+            Some(size) => align_down(size, SECTOR_SIZE),
+            None => align_up(minimum_size, ALIGNMENT)
+        };
+
+        if minimum_size > image_size {
+            return Err(LayoutError::TooSmall { needed: minimum_size, image_size });
+        }
+
+        let mut order = (0..partitions.len()).collect::<Vec<_>>();
+        order.sort_by_key(|i| partitions[*i].offset);
+        for pair in order.windows(2) {
+            if partitions[pair[0]].end() > partitions[pair[1]].offset {
+                return Err(LayoutError::Overlap(config.partitions[pair[0]].label.clone(), config.partitions[pair[1]].label.clone()));
+            }
+        }
+
+        Ok(Self { image_size, minimum_size, partitions })
+    }
+}
+
+/// Formats a byte count using the largest binary unit it reaches, such as `16 MiB` or `1.5 GiB`.
+pub fn format_size(bytes: u64) -> String {
+    for (shift, unit) in [(40, "TiB"), (30, "GiB"), (20, "MiB"), (10, "KiB")] {
+        if bytes >= 1 << shift {
+            return match bytes % (1 << shift) {
+                0 => format!("{} {}", bytes >> shift, unit),
+                _ => format!("{:.1} {}", bytes as f64 / (1u64 << shift) as f64, unit)
+            };
+        }
+    }
+
+    format!("{} B", bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::build::*;
+    use crate::build::testing::*;
+
+    fn swap(label: &str, offset: Option<u64>, size: Option<u64>) -> Partition {
+        partition(label, PartitionPreset::Swap, offset, size)
+    }
+
+    #[test]
+    pub fn places_partitions() {
+        let layout = Layout::new(&config(vec![
+            swap("boot", Some(1 << 20), Some(1 << 20)),
+            swap("root", None, None),
+            swap("swap", None, Some(1000))
+        ], Some(16 << 20))).unwrap();
+
+        assert_eq!(layout.image_size, 16 << 20);
+        assert_eq!(layout.minimum_size, (15 << 20) + 1024 + TABLE_TAIL);
+        assert_eq!(layout.partitions, vec![
+            Extent { offset: 1 << 20, size: 1 << 20 },
+            Extent { offset: 2 << 20, size: 13 << 20 },
+            Extent { offset: 15 << 20, size: 1024 }
+        ]);
+    }
+
+    #[test]
+    pub fn computes_minimum_size() {
+        let layout = Layout::new(&config(vec![
+            swap("boot", None, Some(1 << 20)),
+            swap("root", None, Some(3 << 20))
+        ], None)).unwrap();
+
+        assert_eq!(layout.partitions, vec![
+            Extent { offset: 1 << 20, size: 1 << 20 },
+            Extent { offset: 2 << 20, size: 3 << 20 }
+        ]);
+        assert_eq!(layout.minimum_size, (5 << 20) + TABLE_TAIL);
+        assert_eq!(layout.image_size, 6 << 20);
+    }
+
+    #[test]
+    pub fn rejects_impossible_layouts() {
+        let error = |partitions, image_size| Layout::new(&config(partitions, image_size)).unwrap_err().to_string();
+
+        assert_eq!(error(vec![swap("root", None, None)], None), "partition 'root' has no size, and the image has no size for it to fill");
+        assert_eq!(error(vec![swap("a", None, None), swap("b", None, None)], Some(16 << 20)), "partitions 'a' and 'b' both have no size, so there's no telling how to split the space between them");
+        assert_eq!(error(vec![swap("a", None, None), swap("b", None, Some(15 << 20))], Some(16 << 20)), "partition 'a' has no size, but the partitions after it need 15 MiB of the 15.0 MiB left");
+        assert_eq!(error(vec![swap("a", Some(1 << 20), Some(2 << 20)), swap("b", Some(2 << 20), Some(1 << 20))], None), "partitions 'a' and 'b' overlap");
+        assert_eq!(error(vec![swap("a", Some(1000), Some(1 << 20))], None), "partition 'a' starts at byte 1000, which isn't a multiple of the 512 byte sector size");
+        assert_eq!(error(vec![swap("a", None, Some(16 << 20))], Some(16 << 20)), "the partitions need an image of at least 17.0 MiB, but it's only 16 MiB");
+    }
+
+    #[test]
+    pub fn formats_sizes() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(16 << 20), "16 MiB");
+        assert_eq!(format_size(3 << 29), "1.5 GiB");
+    }
+}
@@ -0,0 +1,128 @@
+//! Configs as they were written before some variants gained fields, such as a bare `Raw` partition or a
+//! `Static("…")` node. Anything which doesn't parse as a current `BuildConfig` is tried again with these types, which
+//! are then converted.
+
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::build;
+use crate::build::Command;
+use crate::build::GitOrigin;
+
+#[derive(Deserialize)]
+pub struct BuildConfig {
+    name: String,
+    partitions: Vec<Partition>,
+    image_size: Option<u64>
+}
+
+#[derive(Deserialize)]
+struct Partition {
+    label: String,
+    purpose: PartitionPreset,
+    offset: Option<u64>,
+    size: Option<u64>
+}
+
+#[derive(Deserialize)]
+struct FSNode {
+    path: PathBuf,
+    source: NodeSource,
+    dependencies: Option<Vec<PathBuf>>
+}
+
+#[derive(Deserialize)]
+enum NodeSource {
+    Static(String),
+    Prebuilt(String),
+    Cargo {
+        repo: String,
+        origin: Option<GitOrigin>,
+        target: Option<String>,
+        features: Option<Vec<String>>
+    },
+    Custom(Command)
+}
+
+#[derive(Deserialize)]
+enum PartitionPreset {
+    Ext4 {
+        filesystem: Vec<FSNode>,
+        post_run: Option<Command>
+    },
+    Fat32 {
+        filesystem: Vec<FSNode>,
+        post_run: Option<Command>
+    },
+    RedoxFS {
+        filesystem: Vec<FSNode>,
+        post_run: Option<Command>
+    },
+    Boot {
+        bios: Option<PathBuf>,
+        uefi: Option<PathBuf>,
+        post_run: Command
+    },
+    Swap,
+    Raw
+}
+
+impl From<BuildConfig> for build::BuildConfig {
+    fn from(config: BuildConfig) -> Self {
+        Self {
+            name: config.name,
+            partitions: config.partitions.into_iter().map(build::Partition::from).collect(),
+            image_size: config.image_size
+        }
+    }
+}
+
+impl From<Partition> for build::Partition {
+    fn from(partition: Partition) -> Self {
+        Self {
+            label: partition.label,
+            purpose: partition.purpose.into(),
+            offset: partition.offset,
+            size: partition.size
+        }
+    }
+}
+
+impl From<PartitionPreset> for build::PartitionPreset {
+    fn from(preset: PartitionPreset) -> Self {
+        match preset {
+            PartitionPreset::Ext4 { filesystem, post_run } => Self::Ext4 { filesystem: nodes(filesystem), post_run },
+            PartitionPreset::Fat32 { filesystem, post_run } => Self::Fat32 { filesystem: nodes(filesystem), post_run },
+            PartitionPreset::RedoxFS { filesystem, post_run } => Self::RedoxFS { filesystem: nodes(filesystem), post_run },
+            PartitionPreset::Boot { bios, uefi, post_run } => Self::Boot { bios, uefi, post_run },
+            PartitionPreset::Swap => Self::Swap,
+            PartitionPreset::Raw => Self::Raw { source: None, fill: None }
+        }
+    }
+}
+
+impl From<FSNode> for build::FSNode {
+    fn from(node: FSNode) -> Self {
+        Self {
+            path: node.path,
+            source: node.source.into(),
+            dependencies: node.dependencies
+        }
+    }
+}
+
+impl From<NodeSource> for build::NodeSource {
+    fn from(source: NodeSource) -> Self {
+        match source {
+            NodeSource::Static(location) => Self::Static { location, sha256: None },
+            NodeSource::Prebuilt(location) => Self::Prebuilt { location, sha256: None },
+            NodeSource::Cargo { repo, origin, target, features } => Self::Cargo { repo, origin, target, features },
+            NodeSource::Custom(command) => Self::Custom(command)
+        }
+    }
+}
+
+fn nodes(filesystem: Vec<FSNode>) -> Vec<build::FSNode> {
+    filesystem.into_iter().map(build::FSNode::from).collect()
+}
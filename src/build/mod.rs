@@ -1,20 +1,60 @@
+use std::cell::Cell;
+use std::path::PathBuf;
+
 use vizia::icons::*;
 use vizia::prelude::*;
 
 use crate::AppEvent;
 use crate::AppState;
 use crate::components::*;
+use crate::edit::parse_size;
+use crate::edit::Editor;
 
+mod artifact;
+mod boot;
 mod build;
+mod cache;
+mod cargo;
+mod command;
 mod config;
+mod ext4;
+mod fat;
+mod format;
+mod gpt;
+mod job;
+mod layout;
+mod legacy;
+mod raw;
+mod redoxfs;
+mod region;
+mod schedule;
+mod swap;
+#[cfg(test)]
+mod testing;
+mod validate;
 
+pub use artifact::*;
+pub use boot::*;
+pub use build::*;
+pub use cache::*;
+pub use cargo::*;
+pub use command::*;
 pub use config::*;
+pub use ext4::*;
+pub use fat::*;
+pub use format::*;
+pub use gpt::*;
+pub use job::*;
+pub use layout::*;
+pub use raw::*;
+pub use redoxfs::*;
+pub use region::*;
+pub use schedule::*;
+pub use swap::*;
+pub use validate::*;
 pub use table::*;
 
-#[derive(Lens)]
-pub struct Builder {
-    config: config::BuildConfig,
-}
+pub struct Builder;
 
 impl View for Builder {
     fn element(&self) -> Option<&'static str> {
@@ -23,10 +63,8 @@ impl View for Builder {
 }
 
 impl Builder {
-    pub fn new(cx: &mut Context, config: config::BuildConfig) -> Handle<Self> {
-        View::build(Self {
-            config: config.clone()
-        }, cx, |cx| {            
+    pub fn new(cx: &mut Context) -> Handle<Self> {
+        View::build(Self, cx, |cx| {            
             Binding::new(cx, AppState::started, |cx, started| {
                 if let Some(started) = started.get(cx) {
                     VStack::new(cx, |cx| {
@@ -50,18 +88,13 @@ impl Builder {
                         })
                         .height(Auto);
 
-                        Table::new(cx, ["Status", "Job", "Elapsed"], vec![
-                            TableRowBuilder::new()
-                                .add_column("Status", |cx| {
-                                    Label::new(cx, "Hello World!");
-                                })
-                        ])
-                            .class("field");
+                        Self::job_table(cx);
+                        Self::job_details(cx);
                         HStack::new(cx, |cx| {
                             RelativeTime::new(cx, AppState::started);
                             Element::new(cx).width(Units::Stretch(1.0));
 
-                            Label::new(cx, Builder::config.map(|i| String::from(&i.name)));
+                            Label::new(cx, AppState::config.map(|i| i.as_ref().map(|i| i.name.clone()).unwrap_or_default()));
                         })
                         .id("status-bar");
                     });
@@ -78,11 +111,12 @@ impl Builder {
                                     })
                                 },
                             )
-                            .class("primary");
+                            .class("primary")
+                            .disabled(AppState::config.map(|i| i.as_ref().map(|i| has_errors(&i.validate())).unwrap_or(true)));
 
                             Button::new(
                                 cx,
-                                |_event| {},
+                                |e| e.emit(AppEvent::Edit(true)),
                                 |cx| {
                                     HStack::new(cx, |cx| {
                                         Label::new(cx, ICON_PENCIL);
@@ -91,21 +125,84 @@ impl Builder {
                                 },
                             )
                             .class("secondary");
+
+                            Button::new(
+                                cx,
+                                |e| e.emit(AppEvent::Save),
+                                |cx| {
+                                    HStack::new(cx, |cx| {
+                                        Label::new(cx, ICON_DEVICE_FLOPPY);
+                                        Label::new(cx, "Save");
+                                    })
+                                },
+                            )
+                            .class("secondary");
+
+                            Button::new(
+                                cx,
+                                |e| e.emit(AppEvent::SaveAs),
+                                |cx| {
+                                    HStack::new(cx, |cx| {
+                                        Label::new(cx, ICON_FILE_EXPORT);
+                                        Label::new(cx, "Save As");
+                                    })
+                                },
+                            )
+                            .class("secondary");
                         })
                         .height(Auto);
 
-                        VStack::new(cx, |cx| {
-                            Label::new(cx, "No build running")
-                            .height(Units::Stretch(1.0));
-                        })
-                        .child_space(Stretch(1.0))
-                        .col_between(Pixels(20.0));
+                        Self::layout(cx);
+                        Self::cache(cx);
+
+                        Binding::new(cx, AppState::editing, |cx, editing| {
+                            if editing.get(cx) {
+                                Editor::new(cx);
+                            } else {
+                                let diagnostics = AppState::config.map(|i| i.as_ref().map(BuildConfig::validate).unwrap_or_default());
+                                Binding::new(cx, diagnostics, |cx, diagnostics| {
+                                    let diagnostics = diagnostics.get(cx);
+                                    if diagnostics.is_empty() {
+                                        // The last build's jobs stay around so their logs can still be read
+                                        Binding::new(cx, AppState::jobs.map(Vec::is_empty), |cx, empty| {
+                                            if empty.get(cx) {
+                                                VStack::new(cx, |cx| {
+                                                    Label::new(cx, "No build running")
+                                                    .height(Units::Stretch(1.0));
+                                                })
+                                                .child_space(Stretch(1.0))
+                                                .col_between(Pixels(20.0));
+                                            } else {
+                                                Self::job_table(cx);
+                                                Self::job_details(cx);
+                                            }
+                                        });
+                                    } else {
+                                        VStack::new(cx, |cx| {
+                                            for diagnostic in diagnostics {
+                                                HStack::new(cx, |cx| {
+                                                    Label::new(cx, match diagnostic.severity {
+                                                        Severity::Warning => ICON_ALERT_TRIANGLE,
+                                                        Severity::Error => ICON_CIRCLE_X
+                                                    });
+                                                    Label::new(cx, diagnostic.message.clone());
+                                                })
+                                                .class("diagnostic")
+                                                .toggle_class("error", diagnostic.severity == Severity::Error)
+                                                .height(Auto);
+                                            }
+                                        })
+                                        .class("diagnostics");
+                                    }
+                                });
+                            }
+                        });
 
                         HStack::new(cx, |cx| {
                             RelativeTime::new(cx, AppState::started);
                             Element::new(cx).width(Units::Stretch(1.0));
 
-                            Label::new(cx, Builder::config.map(|i| String::from(&i.name)));
+                            Label::new(cx, AppState::config.map(|i| i.as_ref().map(|i| i.name.clone()).unwrap_or_default()));
                         })
                         .id("status-bar");
                     });
@@ -113,4 +210,226 @@ impl Builder {
             });
         })
     }
+
+    /// One row for each job of the running build. Rows follow the build's events through their own bindings, and elapsed
+    /// times tick along with `AppEvent::Refresh`.
+    fn job_table(cx: &mut Context) {
+        // The rows are sorted again whenever a job's status changes, since that's also when it starts, finishes or
+        // gets its cache state. The other keys stay the same throughout a build
+        Table::new(cx, ["Status", "Job", "Partition", "Source", "Cache", "Elapsed"], AppState::jobs, |job: &Job| job.status, |cx, index| {
+            let job = AppState::jobs.index(index);
+            let partition = job.map(partition_index).get(cx);
+            let partition = AppState::config.map(move |config| partition_label(config.as_ref(), partition)).get(cx);
+            let source = job.map(source_name).get(cx);
+
+            TableRowBuilder::new()
+                .add_sorted_column("Status", job.map(|job| job.status as i64).get(cx), move |cx| {
+                    Label::new(cx, job.map(|job| status_icon(job.status)))
+                        .class("job-status")
+                        .toggle_class("running", job.map(|job| job.status == JobStatus::Running))
+                        .toggle_class("succeeded", job.map(|job| matches!(job.status, JobStatus::Succeeded | JobStatus::Cached)))
+                        .toggle_class("failed", job.map(|job| job.status == JobStatus::Failed));
+                })
+                .add_sorted_column("Job", job.map(|job| job.name.clone()).get(cx), move |cx| {
+                    Label::new(cx, job.map(|job| job.name.clone()));
+                })
+                .add_sorted_column("Partition", partition, move |cx| {
+                    Binding::new(cx, job.map(partition_index), |cx, partition| {
+                        let partition = partition.get(cx);
+                        Label::new(cx, AppState::config.map(move |config| partition_label(config.as_ref(), partition).unwrap_or_default()));
+                    });
+                })
+                .add_sorted_column("Source", source, move |cx| {
+                    Label::new(cx, job.map(|job| source_name(job).unwrap_or_default()));
+                })
+                .add_sorted_column("Cache", job.map(cache_state).get(cx), move |cx| {
+                    Label::new(cx, job.map(|job| cache_state(job).unwrap_or_default()));
+                })
+                .add_sorted_column("Elapsed", job.map(elapsed).get(cx), move |cx| {
+                    Label::new(cx, job.map(|job| elapsed(job).map(format_elapsed).unwrap_or_default()));
+                })
+                .on_press(move |cx| cx.emit(AppEvent::SelectJob(Some(index))))
+                .toggle_class("selected", AppState::selected.map(move |i| *i == Some(index)))
+        })
+            .hide_columns(["Partition", "Source", "Cache"])
+            .class("field");
+    }
+
+    /// The error and output of the job selected in the table.
+    fn job_details(cx: &mut Context) {
+        Binding::new(cx, AppState::selected, |cx, selected| {
+            let Some(id) = selected.get(cx) else { return; };
+            let job = AppState::jobs.index(id);
+
+            VStack::new(cx, |cx| {
+                Label::new(cx, job.map(|job| job.name.clone()))
+                    .class("job-details-heading");
+
+                Binding::new(cx, job.map(|job| job.error.as_ref().map(ToString::to_string)), |cx, error| {
+                    if let Some(error) = error.get(cx) {
+                        HStack::new(cx, |cx| {
+                            Label::new(cx, ICON_CIRCLE_X);
+                            Label::new(cx, error);
+                        })
+                        .class("diagnostic")
+                        .class("error")
+                        .height(Auto);
+                    }
+                });
+
+                ScrollView::new(cx, 0.0, 0.0, false, true, move |cx| {
+                    // Logs only grow while a job is selected, since queueing a build clears the selection, so labels
+                    // are only added for new lines rather than rebuilding the whole log each time one is printed.
+                    // Printed lines never change, so the labels don't bind to them
+                    let lines = cx.current();
+                    let shown = Cell::new(0);
+                    Binding::new(cx, job.map(|job| job.log.len()), move |cx, len| {
+                        let len = len.get(cx);
+                        cx.with_current(lines, |cx| for line in shown.get()..len {
+                            let text = job.map(move |job| job.log.get(line).cloned().unwrap_or_default()).get(cx);
+                            Label::new(cx, text).class("job-log-line");
+                        });
+                        shown.set(len);
+                    });
+                })
+                .class("job-log");
+            })
+            .class("job-details");
+        });
+    }
+
+    /// Where builds keep their cache and how large it may grow. Unlike the rest of the builder, this isn't part of the
+    /// config, since every config shares the same cache.
+    fn cache(cx: &mut Context) {
+        HStack::new(cx, |cx| {
+            Label::new(cx, "Cache");
+            Textbox::new(cx, AppState::cache_dir.map(|i| i.display().to_string()))
+                .on_submit(|cx, text, _| if !text.trim().is_empty() {
+                    cx.emit(AppEvent::SetCacheDir(PathBuf::from(text)));
+                })
+                .width(Stretch(1.0));
+
+            Label::new(cx, "Limit");
+            Textbox::new(cx, AppState::cache_limit.map(|i| i.map(|i| i.to_string()).unwrap_or_default()))
+                .on_submit(|cx, text, _| if let Some(limit) = parse_size(&text) {
+                    cx.emit(AppEvent::SetCacheLimit(limit));
+                })
+                .width(Pixels(80.0));
+
+            Button::new(
+                cx,
+                |e| e.emit(AppEvent::ClearCache),
+                |cx| {
+                    HStack::new(cx, |cx| {
+                        Label::new(cx, ICON_TRASH);
+                        Label::new(cx, "Clear");
+                    })
+                },
+            )
+            .class("secondary");
+        })
+        .class("cache-settings");
+    }
+
+    /// Shows where each partition will end up in the image. Layout errors are left to the diagnostics.
+    fn layout(cx: &mut Context) {
+        Binding::new(cx, AppState::config, |cx, config| {
+            let Some(config) = config.get(cx) else { return; };
+            let Ok(layout) = Layout::new(&config) else { return; };
+
+            VStack::new(cx, |cx| {
+                HStack::new(cx, |cx| {
+                    Label::new(cx, "Layout");
+                    Element::new(cx).width(Stretch(1.0));
+                    Label::new(cx, match config.image_size {
+                        Some(_) => format!("{} image, {} used", format_size(layout.image_size), format_size(layout.minimum_size)),
+                        None => format!("{} image (smallest that fits)", format_size(layout.image_size))
+                    });
+                })
+                .class("layout-heading");
+
+                let mut order = (0..layout.partitions.len()).collect::<Vec<_>>();
+                order.sort_by_key(|i| layout.partitions[*i].offset);
+
+                HStack::new(cx, |cx| {
+                    let mut cursor = 0;
+                    for index in &order {
+                        let extent = layout.partitions[*index];
+                        Element::new(cx)
+                            .width(Stretch((extent.offset - cursor) as f32))
+                            .class("layout-gap");
+                        Label::new(cx, config.partitions[*index].label.clone())
+                            .width(Stretch(extent.size as f32))
+                            .class("layout-partition");
+                        cursor = extent.end();
+                    }
+
+                    Element::new(cx)
+                        .width(Stretch((layout.image_size - cursor) as f32))
+                        .class("layout-gap");
+                })
+                .class("layout-bar");
+
+                for index in order {
+                    let extent = layout.partitions[index];
+                    HStack::new(cx, |cx| {
+                        Label::new(cx, config.partitions[index].label.clone()).width(Stretch(1.0));
+                        Label::new(cx, config.partitions[index].purpose.kind().name()).width(Stretch(1.0));
+                        Label::new(cx, format!("at {}", format_size(extent.offset))).width(Stretch(1.0));
+                        Label::new(cx, format_size(extent.size)).width(Stretch(1.0));
+                    })
+                    .class("layout-row");
+                }
+            })
+            .class("layout");
+        });
+    }
+}
+
+/// Which partition the job builds, or builds a file of.
+fn partition_index(job: &Job) -> Option<usize> {
+    match &job.kind {
+        JobKind::Node { partition, .. } => Some(*partition),
+        JobKind::Partition { index, .. } => Some(*index),
+        JobKind::Table => None
+    }
+}
+
+fn partition_label(config: Option<&BuildConfig>, partition: Option<usize>) -> Option<String> {
+    config?.partitions.get(partition?).map(|i| i.label.clone())
+}
+
+/// Where a node job's file comes from.
+fn source_name(job: &Job) -> Option<&'static str> {
+    match &job.kind {
+        JobKind::Node { node, .. } => Some(node.source.kind().name()),
+        _ => None
+    }
+}
+
+/// Whether a node's output came from the cache. Only nodes other than custom commands are cached, so other jobs have
+/// nothing to say here.
+fn cache_state(job: &Job) -> Option<&'static str> {
+    match (&job.kind, job.status) {
+        (JobKind::Node { node, .. }, _) if matches!(node.source, NodeSource::Custom(_)) => None,
+        (JobKind::Node { .. }, JobStatus::Cached) => Some("Hit"),
+        (JobKind::Node { .. }, JobStatus::Succeeded) => Some("Miss"),
+        _ => None
+    }
+}
+
+/// How long the job has been running, or took to run.
+fn elapsed(job: &Job) -> Option<Duration> {
+    job.started.map(|started| job.finished.unwrap_or_else(Instant::now) - started)
+}
+
+fn status_icon(status: JobStatus) -> &'static str {
+    match status {
+        JobStatus::Pending => ICON_CLOCK,
+        JobStatus::Running => ICON_LOADER_2,
+        JobStatus::Succeeded => ICON_CIRCLE_CHECK,
+        JobStatus::Cached => ICON_DATABASE,
+        JobStatus::Failed => ICON_CIRCLE_X,
+        JobStatus::Cancelled => ICON_BAN
+    }
 }
@@ -0,0 +1,67 @@
+use std::fs::File;
+use std::io;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+
+use crate::build::*;
+
+/// Fills `region` with `fill`, then copies `source` over the start of it. A zero fill is skipped, since the image is
+/// created empty and writing zeroes would only make it less sparse.
+pub fn write_raw(region: &mut Region, source: Option<&Path>, fill: u8) -> io::Result<()> {
+    let size = region.extent().size;
+
+    if fill != 0 {
+        let chunk = vec![fill; 1 << 20];
+        region.seek(SeekFrom::Start(0))?;
+
+        let mut remaining = size;
+        while remaining > 0 {
+            let len = remaining.min(chunk.len() as u64);
+            region.write_all(&chunk[..len as usize])?;
+            remaining -= len;
+        }
+    }
+
+    if let Some(source) = source {
+        let mut file = File::open(source)?;
+        let len = file.metadata()?.len();
+        if len > size {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is {}, but the partition is only {}", source.display(), format_size(len), format_size(size))));
+        }
+
+        region.seek(SeekFrom::Start(0))?;
+        io::copy(&mut file, region)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::build::*;
+    use crate::build::testing::*;
+
+    #[test]
+    pub fn fills_partition() {
+        let dir = TempDir::new("raw");
+        fs::write(dir.join("source.bin"), b"data").unwrap();
+
+        let image = dir.join("image.img");
+        fs::File::create(&image).unwrap().set_len(4096).unwrap();
+        let extent = Extent { offset: 1024, size: 2048 };
+        write_raw(&mut Region::open(&image, extent).unwrap(), Some(&dir.join("source.bin")), 0xFF).unwrap();
+
+        let content = fs::read(&image).unwrap();
+        assert!(content[..1024].iter().all(|i| *i == 0));
+        assert_eq!(&content[1024..1028], b"data");
+        assert!(content[1028..3072].iter().all(|i| *i == 0xFF));
+        assert!(content[3072..].iter().all(|i| *i == 0));
+
+        let small = Extent { offset: 0, size: 2 };
+        assert!(write_raw(&mut Region::open(&image, small).unwrap(), Some(&dir.join("source.bin")), 0).is_err());
+    }
+}
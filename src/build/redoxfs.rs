@@ -0,0 +1,64 @@
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use ::redoxfs::DiskIo;
+use ::redoxfs::FileSystem;
+
+use crate::build::*;
+
+fn redoxfs_error(err: impl std::fmt::Display) -> io::Error {
+    io::Error::other(format!("redoxfs: {}", err))
+}
+
+/// Creates a RedoxFS filesystem in `region`, holding the contents of `staging`. Only the blocks the filesystem uses are
+/// written, so the rest of the partition stays sparse in the image.
+pub fn write_redoxfs(region: Region, staging: &Path) -> io::Result<()> {
+    let ctime = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut filesystem = FileSystem::create(DiskIo(region), None, ctime.as_secs(), ctime.subsec_nanos())
+        .map_err(redoxfs_error)?;
+
+    ::redoxfs::archive(&mut filesystem, staging)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use ::redoxfs::DiskIo;
+    use ::redoxfs::FileSystem;
+    use ::redoxfs::TreePtr;
+
+    use crate::build::*;
+    use crate::build::testing::*;
+
+    #[test]
+    pub fn populates_filesystem() {
+        let dir = TempDir::new("redoxfs");
+        let staging = dir.join("staging");
+        fs::create_dir_all(staging.join("etc")).unwrap();
+        fs::write(staging.join("etc/hostname"), "redox\n").unwrap();
+
+        let image = dir.join("image.img");
+        fs::File::create(&image).unwrap().set_len(18 << 20).unwrap();
+        let extent = Extent { offset: 1 << 20, size: 16 << 20 };
+        write_redoxfs(Region::open(&image, extent).unwrap(), &staging).unwrap();
+
+        // Nothing outside the partition is touched, and the unused part of it is never written
+        let metadata = fs::metadata(&image).unwrap();
+        assert_eq!(metadata.len(), 18 << 20);
+        assert!(std::os::unix::fs::MetadataExt::blocks(&metadata) * 512 < extent.size);
+
+        let mut filesystem = FileSystem::open(DiskIo(Region::open(&image, extent).unwrap()), None, None, false).unwrap();
+        let content = filesystem.tx(|tx| {
+            let etc = tx.find_node(TreePtr::root(), "etc")?;
+            let hostname = tx.find_node(etc.ptr(), "hostname")?;
+            let mut content = vec![0; hostname.data().size() as usize];
+            tx.read_node(hostname.ptr(), 0, &mut content, 0, 0)?;
+            Ok(content)
+        }).unwrap();
+        assert_eq!(content, b"redox\n");
+    }
+}
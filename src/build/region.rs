@@ -0,0 +1,75 @@
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+
+use crate::build::*;
+
+/// One partition's byte range of the image file, which filesystem writers can treat as a disk of its own. Reads and
+/// writes stop at the end of the range.
+#[derive(Debug)]
+pub struct Region {
+    file: File,
+    extent: Extent,
+    position: u64
+}
+
+impl Region {
+    pub fn open(image: &Path, extent: Extent) -> io::Result<Self> {
+        Ok(Self {
+            file: OpenOptions::new().read(true).write(true).open(image)?,
+            extent,
+            position: 0
+        })
+    }
+
+    pub fn extent(&self) -> Extent {
+        self.extent
+    }
+
+    /// How many bytes can be read or written from the current position, capped at `len`.
+    fn available(&self, len: usize) -> usize {
+        self.extent.size.saturating_sub(self.position).min(len as u64) as usize
+    }
+}
+
+impl Read for Region {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.available(buf.len());
+        self.file.seek(SeekFrom::Start(self.extent.offset + self.position))?;
+        let read = self.file.read(&mut buf[..len])?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Write for Region {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.available(buf.len());
+        self.file.seek(SeekFrom::Start(self.extent.offset + self.position))?;
+        let written = self.file.write(&buf[..len])?;
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for Region {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.extent.size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset)
+        };
+
+        self.position = position.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before the start of the partition"))?;
+        Ok(self.position)
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::build::*;
+
+#[derive(Debug)]
+pub enum ScheduleError {
+    /// Each entry names a node and the dependency path that no node in the config provides.
+    Dangling(Vec<(String, PathBuf)>),
+    /// The names of the nodes forming the cycle, in dependency order, starting and ending with the same node.
+    Cycle(Vec<String>)
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::Dangling(dangling) => {
+                let missing = dangling.iter()
+                    .map(|(node, path)| format!("{} depends on {}", node, path.display()))
+                    .collect::<Vec<_>>();
+                write!(f, "no node provides what these depend on: {}", missing.join(", "))
+            },
+            ScheduleError::Cycle(cycle) => write!(f, "nodes depend on each other in a cycle: {}", cycle.join(" -> "))
+        }
+    }
+}
+
+/// The dependency graph between the jobs of a build, indexed by job id.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    pub dependencies: Vec<Vec<usize>>,
+    pub dependents: Vec<Vec<usize>>
+}
+
+impl Schedule {
+    /// Node jobs depend on every node providing one of their `FSNode::dependencies`, in any partition.
+    /// A partition job depends on all of its own nodes, and on the partition before it, since they share the image. The
+    /// first partition waits for the partition table instead, which creates the image.
+    pub fn new(jobs: &[Job]) -> Result<Self, ScheduleError> {
+        let mut providers: HashMap<&PathBuf, Vec<usize>> = HashMap::new();
+        for job in jobs {
+            if let JobKind::Node { node, .. } = &job.kind {
+                providers.entry(&node.path).or_default().push(job.id);
+            }
+        }
+
+        let mut dangling = vec![];
+        let mut previous_partition = jobs.iter()
+            .find(|i| i.kind == JobKind::Table)
+            .map(|i| i.id);
+        let dependencies = jobs.iter()
+            .map(|job| match &job.kind {
+                JobKind::Node { node, .. } => node.dependencies.iter()
+                    .flatten()
+                    .flat_map(|path| match providers.get(path) {
+                        Some(ids) => ids.clone(),
+                        None => {
+                            dangling.push((job.name.clone(), path.clone()));
+                            vec![]
+                        }
+                    })
+                    .collect(),
+                JobKind::Table => vec![],
+                JobKind::Partition { index, .. } => {
+                    let nodes = jobs.iter()
+                        .filter(|i| matches!(i.kind, JobKind::Node { partition, .. } if partition == *index))
+                        .map(|i| i.id);
+
+                    let deps = nodes.chain(previous_partition).collect();
+                    previous_partition = Some(job.id);
+                    deps
+                }
+            })
+            .collect::<Vec<Vec<usize>>>();
+
+        if !dangling.is_empty() {
+            return Err(ScheduleError::Dangling(dangling));
+        }
+
+        let mut dependents = vec![vec![]; jobs.len()];
+        for (job, deps) in dependencies.iter().enumerate() {
+            for dep in deps {
+                dependents[*dep].push(job);
+            }
+        }
+
+        let schedule = Self { dependencies, dependents };
+
+        if let Some(cycle) = schedule.find_cycle() {
+            return Err(ScheduleError::Cycle(cycle.into_iter()
+                .map(|i| jobs[i].name.clone())
+                .collect()));
+        }
+
+        Ok(schedule)
+    }
+
+    /// The jobs which have no dependencies and can start straight away.
+    pub fn roots(&self) -> impl Iterator<Item=usize> + '_ {
+        self.dependencies.iter()
+            .enumerate()
+            .filter(|(_, deps)| deps.is_empty())
+            .map(|(id, _)| id)
+    }
+
+    /// Returns the jobs along one cycle, if there is any, with the first job repeated at the end.
+    fn find_cycle(&self) -> Option<Vec<usize>> {
+        let mut remaining = self.dependencies.iter()
+            .map(|i| i.len())
+            .collect::<Vec<_>>();
+        let mut queue = self.roots().collect::<VecDeque<_>>();
+
+        while let Some(job) = queue.pop_front() {
+            for dependent in &self.dependents[job] {
+                remaining[*dependent] -= 1;
+                if remaining[*dependent] == 0 {
+                    queue.push_back(*dependent);
+                }
+            }
+        }
+
+        // Every job left over still waits on another left-over job, so following those edges has to loop eventually.
+        let mut job = remaining.iter().position(|i| *i > 0)?;
+        let mut path = vec![];
+        while !path.contains(&job) {
+            path.push(job);
+            job = *self.dependencies[job].iter().find(|i| remaining[**i] > 0)?;
+        }
+
+        let start = path.iter().position(|i| *i == job)?;
+        let mut cycle = path.split_off(start);
+        cycle.push(job);
+
+        Some(cycle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::build::*;
+    use crate::build::testing::*;
+
+    fn node(path: &str, dependencies: &[&str]) -> FSNode {
+        FSNode {
+            path: PathBuf::from(path),
+            source: NodeSource::Static { location: path.to_owned(), sha256: None },
+            dependencies: Some(dependencies.iter().map(PathBuf::from).collect())
+        }
+    }
+
+    fn root(filesystem: Vec<FSNode>) -> BuildConfig {
+        config(vec![partition("root", PartitionPreset::RedoxFS { filesystem, post_run: None }, None, None)], None)
+    }
+
+    #[test]
+    pub fn orders_dependencies() {
+        let jobs = jobs(&root(vec![node("/bin/a", &["/bin/b"]), node("/bin/b", &[])]));
+        let schedule = Schedule::new(&jobs).unwrap();
+
+        assert_eq!(schedule.roots().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(schedule.dependencies[0], vec![1]);
+        assert!(schedule.dependencies[2].is_empty());
+        assert_eq!(schedule.dependencies[3], vec![0, 1, 2]);
+    }
+
+    #[test]
+    pub fn rejects_cycles() {
+        let jobs = jobs(&root(vec![node("/bin/a", &["/bin/b"]), node("/bin/b", &["/bin/c"]), node("/bin/c", &["/bin/a"])]));
+
+        match Schedule::new(&jobs) {
+            Err(err @ ScheduleError::Cycle(_)) => assert_eq!(
+                err.to_string(),
+                "nodes depend on each other in a cycle: root:/bin/a -> root:/bin/b -> root:/bin/c -> root:/bin/a"
+            ),
+            other => panic!("expected a cycle, got {:?}", other)
+        }
+    }
+
+    #[test]
+    pub fn rejects_dangling_dependencies() {
+        let jobs = jobs(&root(vec![node("/bin/a", &["/bin/missing"])]));
+
+        match Schedule::new(&jobs) {
+            Err(ScheduleError::Dangling(dangling)) => assert_eq!(dangling, vec![("root:/bin/a".to_owned(), PathBuf::from("/bin/missing"))]),
+            other => panic!("expected a dangling dependency, got {:?}", other)
+        }
+    }
+}
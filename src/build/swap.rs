@@ -0,0 +1,65 @@
+use std::io;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+
+use uuid::Uuid;
+
+use crate::build::*;
+
+/// Linux swap pages are always 4 KiB in the header, whatever the page size of the machine.
+const PAGE_SIZE: u64 = 4096;
+/// `mkswap` refuses to make anything smaller than this many pages.
+const MINIMUM_PAGES: u64 = 10;
+
+/// Writes a version 1 Linux swap header (as `mkswap` would) to the start of `region`. The rest is left alone.
+pub fn write_swap(region: &mut Region, config: &BuildConfig, partition: &Partition) -> io::Result<()> {
+    let pages = region.extent().size / PAGE_SIZE;
+    if pages < MINIMUM_PAGES {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("swap partition '{}' needs at least {}", partition.label, format_size(MINIMUM_PAGES * PAGE_SIZE))));
+    }
+
+    let uuid = Uuid::new_v5(&Uuid::NAMESPACE_OID, format!("{}/{}/swap", config.name, partition.label).as_bytes());
+
+    let mut header = [0u8; PAGE_SIZE as usize];
+    // The first 1024 bytes are left for a boot block
+    header[1024..1028].copy_from_slice(&1u32.to_le_bytes());
+    header[1028..1032].copy_from_slice(&((pages - 1) as u32).to_le_bytes());
+    header[1036..1052].copy_from_slice(uuid.as_bytes());
+    for (byte, label) in header[1052..1068].iter_mut().zip(partition.label.bytes()) {
+        *byte = label;
+    }
+    header[PAGE_SIZE as usize - 10..].copy_from_slice(b"SWAPSPACE2");
+
+    region.seek(SeekFrom::Start(0))?;
+    region.write_all(&header)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::process::Command;
+
+    use crate::build::*;
+    use crate::build::testing::*;
+
+    #[test]
+    pub fn writes_swap_header() {
+        let config = config(vec![partition("swap", PartitionPreset::Swap, None, Some(1 << 20))], None);
+
+        let dir = TempDir::new("swap");
+        let path = dir.join("image.img");
+        fs::File::create(&path).unwrap().set_len(1 << 20).unwrap();
+        let extent = Extent { offset: 0, size: 1 << 20 };
+        write_swap(&mut Region::open(&path, extent).unwrap(), &config, &config.partitions[0]).unwrap();
+
+        let image = fs::read(&path).unwrap();
+        assert_eq!(&image[4086..4096], b"SWAPSPACE2");
+        assert_eq!(u32::from_le_bytes(image[1028..1032].try_into().unwrap()), 255);
+
+        let output = Command::new("blkid").args(["-p", "-o", "export"]).arg(&path).output()
+            .expect("this test needs blkid from util-linux");
+        let output = String::from_utf8_lossy(&output.stdout);
+        assert!(output.contains("TYPE=swap") && output.contains("LABEL=swap"), "{}", output);
+    }
+}
@@ -0,0 +1,48 @@
+use std::fs;
+use std::ops::Deref;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::build::*;
+
+/// A directory for a test to work in, which is removed along with everything in it once dropped, whether or not the
+/// test passed.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Tests run at the same time, so each one needs a `name` of its own.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("guibuild-{}-{}", name, std::process::id()));
+
+        // Whatever an earlier run with the same process ID left behind, if it was killed before cleaning up
+        match fs::remove_dir_all(&path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => panic!("couldn't clear {}: {}", path.display(), err),
+            _ => {}
+        }
+
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+pub fn config(partitions: Vec<Partition>, image_size: Option<u64>) -> BuildConfig {
+    BuildConfig { name: "Test Config".to_owned(), partitions, image_size }
+}
+
+pub fn partition(label: &str, purpose: PartitionPreset, offset: Option<u64>, size: Option<u64>) -> Partition {
+    Partition { label: label.to_owned(), purpose, offset, size }
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use vizia::prelude::*;
+
+use crate::build::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error
+}
+
+/// Where in the config a diagnostic applies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    Config,
+    Partition(usize),
+    Node { partition: usize, node: usize }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub location: Location,
+    pub message: String
+}
+
+impl Data for Diagnostic {
+    fn same(&self, other: &Self) -> bool {
+        *self == *other
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.message),
+            Severity::Error => write!(f, "error: {}", self.message)
+        }
+    }
+}
+
+/// The diagnostics of a config which has errors. They're meant to be listed on their own, so this only says how many
+/// errors there are.
+#[derive(Debug)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.iter().filter(|i| i.severity == Severity::Error).count() {
+            1 => write!(f, "the config has an error"),
+            count => write!(f, "the config has {} errors", count)
+        }
+    }
+}
+
+impl Diagnostic {
+    fn error(location: Location, message: String) -> Self {
+        Self { severity: Severity::Error, location, message }
+    }
+
+    fn warning(location: Location, message: String) -> Self {
+        Self { severity: Severity::Warning, location, message }
+    }
+}
+
+impl BuildConfig {
+    /// Looks for mistakes which would make the config impossible to build, or build something other than intended.
+    /// Parsing alone doesn't catch these. A build is only started if none of the diagnostics are errors.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+
+        if self.partitions.is_empty() {
+            diagnostics.push(Diagnostic::warning(Location::Config, "the image has no partitions".to_owned()));
+        }
+
+        let mut labels: HashMap<&str, usize> = HashMap::new();
+        for (index, partition) in self.partitions.iter().enumerate() {
+            let location = Location::Partition(index);
+
+            if partition.label.is_empty() {
+                diagnostics.push(Diagnostic::error(location.clone(), format!("partition {} has no label", index + 1)));
+            } else if let Some(first) = labels.insert(&partition.label, index) {
+                diagnostics.push(Diagnostic::error(location.clone(), format!("partitions {} and {} are both labelled '{}'", first + 1, index + 1, partition.label)));
+            }
+
+            if matches!(partition.purpose, PartitionPreset::Boot { .. }) && index != 0 {
+                diagnostics.push(Diagnostic::error(location.clone(), format!("boot partition '{}' must be the first partition", partition.label)));
+            }
+
+            if partition.size == Some(0) {
+                diagnostics.push(Diagnostic::error(location.clone(), format!("partition '{}' is empty", partition.label)));
+            }
+
+            if let Some(size) = partition.size.filter(|i| *i > 0 && *i < FAT32_MIN_SIZE && is_fat32(partition)) {
+                diagnostics.push(Diagnostic::error(location.clone(), too_small_for_fat32(partition, size)));
+            }
+
+            if let (Some(image_size), Some(offset), Some(size)) = (self.image_size, partition.offset, partition.size) {
+                if offset.saturating_add(size) > image_size {
+                    diagnostics.push(Diagnostic::error(location.clone(), format!("partition '{}' ends at byte {}, past the end of the {} byte image", partition.label, offset.saturating_add(size), image_size)));
+                }
+            } else if let (Some(image_size), Some(size)) = (self.image_size, partition.size) {
+                if size > image_size {
+                    diagnostics.push(Diagnostic::error(location.clone(), format!("partition '{}' is larger than the {} byte image", partition.label, image_size)));
+                }
+            }
+
+            for (other_index, other) in self.partitions.iter().enumerate().skip(index + 1) {
+                let (Some(a), Some(a_size), Some(b), Some(b_size)) = (partition.offset, partition.size, other.offset, other.size) else { continue; };
+
+                if a < b.saturating_add(b_size) && b < a.saturating_add(a_size) {
+                    diagnostics.push(Diagnostic::error(Location::Partition(other_index), format!("partitions '{}' and '{}' overlap", partition.label, other.label)));
+                }
+            }
+
+            let mut paths = HashMap::new();
+            for (node_index, node) in partition.purpose.filesystem().iter().enumerate() {
+                let location = Location::Node { partition: index, node: node_index };
+
+                if !node.path.is_absolute() {
+                    diagnostics.push(Diagnostic::error(location.clone(), format!("'{}' in partition '{}' must be an absolute path", node.path.display(), partition.label)));
+                }
+
+                if paths.insert(&node.path, node_index).is_some() {
+                    diagnostics.push(Diagnostic::error(location.clone(), format!("'{}' appears more than once in partition '{}'", node.path.display(), partition.label)));
+                }
+
+                match &node.source {
+                    NodeSource::Custom(Command { cmd, .. }) if cmd.is_empty() => {
+                        diagnostics.push(Diagnostic::error(location, format!("'{}' in partition '{}' has an empty command", node.path.display(), partition.label)));
+                    },
+                    NodeSource::Static { location: path, sha256 } | NodeSource::Prebuilt { location: path, sha256 } => {
+                        if let Err(err) = resolve_location(path) {
+                            diagnostics.push(Diagnostic::error(location.clone(), format!("'{}' in partition '{}': {}", node.path.display(), partition.label, err)));
+                        }
+
+                        if sha256.as_deref().is_some_and(|i| !is_sha256(i)) {
+                            diagnostics.push(Diagnostic::error(location, format!("'{}' in partition '{}' has a malformed sha256, which should be 64 hex digits", node.path.display(), partition.label)));
+                        }
+                    },
+                    _ => {}
+                }
+            }
+        }
+
+        let jobs = jobs(self);
+        let node_location = |name: &str| jobs.iter()
+            .find(|i| i.name == name)
+            .and_then(|i| match &i.kind {
+                JobKind::Node { partition, node } => Some(Location::Node {
+                    partition: *partition,
+                    node: self.partitions[*partition].purpose.filesystem().iter().position(|i| i == node)?
+                }),
+                _ => None
+            })
+            .unwrap_or(Location::Config);
+
+        match Schedule::new(&jobs) {
+            Err(ScheduleError::Dangling(dangling)) => for (name, dependency) in dangling {
+                diagnostics.push(Diagnostic::error(node_location(&name), format!("{} depends on '{}', which no node provides", name, dependency.display())));
+            },
+            Err(ScheduleError::Cycle(cycle)) => {
+                diagnostics.push(Diagnostic::error(node_location(&cycle[0]), format!("dependency cycle: {}", cycle.join(" -> "))));
+            },
+            Ok(_) => {}
+        }
+
+        // Most layout problems have been reported against a specific partition already
+        if !has_errors(&diagnostics) {
+            match Layout::new(self) {
+                Err(err) => {
+                    let location = err.label()
+                        .and_then(|label| self.partitions.iter().position(|i| i.label == label))
+                        .map(Location::Partition)
+                        .unwrap_or(Location::Config);
+
+                    diagnostics.push(Diagnostic::error(location, err.to_string()));
+                },
+                // Partitions without a size are only known to be too small once they've been given the leftover space
+                Ok(layout) => for (index, partition) in self.partitions.iter().enumerate() {
+                    let size = layout.partitions[index].size;
+                    if partition.size.is_none() && size < FAT32_MIN_SIZE && is_fat32(partition) {
+                        diagnostics.push(Diagnostic::error(Location::Partition(index), too_small_for_fat32(partition, size)));
+                    }
+                }
+            }
+        }
+
+        diagnostics
+    }
+}
+
+/// Whether the partition is formatted as FAT32, either for its own sake or to hold a UEFI bootloader.
+fn is_fat32(partition: &Partition) -> bool {
+    matches!(partition.purpose, PartitionPreset::Fat32 { .. } | PartitionPreset::Boot { uefi: Some(_), .. })
+}
+
+fn too_small_for_fat32(partition: &Partition, size: u64) -> String {
+    format!("partition '{}' is {} bytes, but FAT32 needs at least {}", partition.label, size, FAT32_MIN_SIZE)
+}
+
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|i| i.severity == Severity::Error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::build::*;
+    use crate::build::testing::*;
+
+    fn redoxfs(paths: &[&str]) -> PartitionPreset {
+        PartitionPreset::RedoxFS {
+            filesystem: paths.iter()
+                .map(|path| FSNode { path: PathBuf::from(path), source: NodeSource::Static { location: path.to_string(), sha256: None }, dependencies: None })
+                .collect(),
+            post_run: None
+        }
+    }
+
+    #[test]
+    pub fn accepts_valid_config() {
+        let config = config(vec![
+            partition("boot", PartitionPreset::Boot { bios: None, uefi: None, post_run: Command { cmd: vec!["true".to_owned()], cwd: None, env: None } }, Some(1 << 20), Some(1 << 20)),
+            partition("root", redoxfs(&["/bin/ion", "/etc/hostname"]), Some(2 << 20), Some(8 << 20)),
+        ], Some(16 << 20));
+
+        assert_eq!(config.validate(), vec![]);
+    }
+
+    #[test]
+    pub fn reports_mistakes() {
+        let config = config(vec![
+            partition("root", redoxfs(&["bin/ion", "/etc/hostname", "/etc/hostname"]), Some(0), Some(8 << 20)),
+            partition("root", PartitionPreset::Swap, Some(4 << 20), Some(16 << 20)),
+            partition("boot", PartitionPreset::Boot { bios: None, uefi: None, post_run: Command { cmd: vec!["true".to_owned()], cwd: None, env: None } }, None, None),
+        ], Some(16 << 20));
+
+        let diagnostics = config.validate();
+        let at = |location: Location| diagnostics.iter()
+            .filter(|i| i.location == location)
+            .map(|i| i.message.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(at(Location::Node { partition: 0, node: 0 }), vec!["'bin/ion' in partition 'root' must be an absolute path"]);
+        assert_eq!(at(Location::Node { partition: 0, node: 2 }), vec!["'/etc/hostname' appears more than once in partition 'root'"]);
+        assert_eq!(at(Location::Partition(1)), vec![
+            "partitions 'root' and 'root' overlap",
+            "partitions 1 and 2 are both labelled 'root'",
+            "partition 'root' ends at byte 20971520, past the end of the 16777216 byte image"
+        ]);
+        assert_eq!(at(Location::Partition(2)), vec!["boot partition 'boot' must be the first partition"]);
+        assert!(has_errors(&diagnostics));
+    }
+
+    #[test]
+    pub fn reports_small_fat32_partitions() {
+        let boot = PartitionPreset::Boot { bios: None, uefi: Some(PathBuf::from("BOOTX64.EFI")), post_run: Command { cmd: vec!["true".to_owned()], cwd: None, env: None } };
+        let fat32 = PartitionPreset::Fat32 { filesystem: vec![], post_run: None };
+        let config = config(vec![
+            partition("efi", boot, None, Some(16 << 20)),
+            partition("data", fat32.clone(), None, Some(FAT32_MIN_SIZE)),
+            partition("rest", fat32, None, None),
+        ], Some(96 << 20));
+
+        let diagnostics = config.validate();
+        let messages = diagnostics.iter().map(|i| (i.location.clone(), i.message.as_str())).collect::<Vec<_>>();
+        assert_eq!(messages[0], (Location::Partition(0), "partition 'efi' is 16777216 bytes, but FAT32 needs at least 34603008"));
+        assert_eq!(messages.len(), 1);
+
+        // Once the other partitions are large enough, the one which gets the leftover space is checked too
+        let mut config = config;
+        config.partitions[0].size = Some(FAT32_MIN_SIZE);
+        let diagnostics = config.validate();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location, Location::Partition(2));
+        assert!(diagnostics[0].message.starts_with("partition 'rest' is "), "{}", diagnostics[0].message);
+    }
+}
@@ -0,0 +1,169 @@
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use clap::Parser;
+use clap::Subcommand;
+
+use crate::build;
+use crate::Error;
+
+/// Builds Redox OS disk images. Without a subcommand, the graphical builder is opened.
+#[derive(Parser)]
+#[command(name = "guibuild", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub action: Option<Action>
+}
+
+#[derive(Subcommand)]
+pub enum Action {
+    /// Build the image described by a configuration file
+    Build {
+        config: PathBuf,
+        /// How many jobs to run at the same time
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        /// Where to put the image and intermediate files
+        #[arg(short, long)]
+        out_dir: Option<PathBuf>,
+        /// Where to keep fetched sources and built files between builds
+        #[arg(long)]
+        cache_dir: Option<PathBuf>,
+        /// How much space built files may take up in the cache, such as 512M or 4GiB
+        #[arg(long, value_parser = parse_limit)]
+        cache_limit: Option<u64>,
+        /// Print the output of every command as it runs
+        #[arg(short, long)]
+        verbose: bool
+    },
+    /// Delete everything in the build cache
+    ClearCache {
+        /// The cache to clear, if not the default one
+        #[arg(long)]
+        cache_dir: Option<PathBuf>
+    },
+    /// Check a configuration file for errors without building it
+    Validate {
+        config: PathBuf
+    },
+    /// Print a configuration file, optionally converting it to another format
+    Print {
+        config: PathBuf,
+        /// One of ron, json, json5 or toml. Defaults to the format of the file
+        #[arg(short, long)]
+        format: Option<build::ConfigFormat>
+    }
+}
+
+pub fn run(action: Action) -> ExitCode {
+    let result = match action {
+        Action::Build { config, jobs, out_dir, cache_dir, cache_limit, verbose } => load(&config).and_then(|(config, _)| {
+            let mut options = build::BuildOptions::for_config(&config);
+            if let Some(jobs) = jobs {
+                options.jobs = jobs;
+            }
+            if let Some(out_dir) = out_dir {
+                options.out_dir = out_dir;
+            }
+            if let Some(cache_dir) = cache_dir {
+                options.cache_dir = cache_dir;
+            }
+            if cache_limit.is_some() {
+                options.cache_limit = cache_limit;
+            }
+
+            run_build(config, options, verbose)
+        }),
+        Action::ClearCache { cache_dir } => {
+            let cache_dir = cache_dir.unwrap_or_else(|| PathBuf::from(build::CACHE_DIR));
+            build::clear_cache(&cache_dir)
+                .map(|()| println!("Cleared {}", cache_dir.display()))
+                .map_err(|err| Arc::new(Error::from(err)))
+        },
+        Action::Validate { config } => load(&config).and_then(|(config, _)| {
+            let diagnostics = config.validate();
+            for diagnostic in &diagnostics {
+                println!("{}", diagnostic);
+            }
+
+            if build::has_errors(&diagnostics) {
+                return Err(Arc::new(Error::from(build::Diagnostics(diagnostics))));
+            }
+
+            println!("{}: ok", config.name);
+            Ok(())
+        }),
+        Action::Print { config, format } => load(&config).and_then(|(config, loaded)| {
+            let content = format.unwrap_or(loaded)
+                .serialize(&config)
+                .map_err(Arc::new)?;
+
+            println!("{}", content);
+            Ok(())
+        })
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_limit(text: &str) -> Result<u64, String> {
+    match crate::edit::parse_size(text) {
+        Some(Some(limit)) => Ok(limit),
+        _ => Err(format!("'{}' isn't a size", text))
+    }
+}
+
+fn load(path: &Path) -> Result<(build::BuildConfig, build::ConfigFormat), Arc<Error>> {
+    build::load(path).map_err(Arc::new)
+}
+
+/// Runs the same build as the GUI, printing a line whenever a job starts or finishes, and with `verbose`, whatever
+/// its commands print.
+fn run_build(config: build::BuildConfig, options: build::BuildOptions, verbose: bool) -> Result<(), Arc<Error>> {
+    let jobs: Mutex<Vec<build::Job>> = Mutex::new(vec![]);
+    let started = Instant::now();
+
+    build::build(config, options, build::BuildHandle::default(), |event| {
+        let mut jobs = jobs.lock().unwrap();
+
+        // Jobs run in whatever order their dependencies allow, so progress is counted in jobs finished so far
+        let total = jobs.len();
+        let done = jobs.iter().filter(|i| i.finished.is_some()).count();
+
+        match event {
+            build::BuildEvent::Queued(queued) => {
+                println!("Queued {} jobs", queued.len());
+                *jobs = queued;
+            },
+            build::BuildEvent::JobStarted { id, at } => if let Some(job) = jobs.get_mut(id) {
+                job.started = Some(at);
+                println!("[{:>3}/{}] started  {}", done, total, job.name);
+            },
+            build::BuildEvent::JobOutput { id, line } => if let Some(job) = jobs.get(id).filter(|_| verbose) {
+                println!("{:>9} {}: {}", "", job.name, line);
+            },
+            build::BuildEvent::JobFinished { id, at, result } => if let Some(job) = jobs.get_mut(id) {
+                job.finished = Some(at);
+                let done = done + 1;
+                let elapsed = job.started.map(|i| at - i).unwrap_or_default();
+                match result {
+                    Ok(build::JobStatus::Cached) => println!("[{:>3}/{}] cached   {}", done, total, job.name),
+                    Ok(_) => println!("[{:>3}/{}] finished {} ({:.1}s)", done, total, job.name, elapsed.as_secs_f32()),
+                    Err(err) => println!("[{:>3}/{}] failed   {} ({:.1}s): {}", done, total, job.name, elapsed.as_secs_f32(), err)
+                }
+            },
+            build::BuildEvent::Finished(Ok(())) => println!("Build finished in {:.1}s", started.elapsed().as_secs_f32()),
+            build::BuildEvent::Finished(Err(_)) => println!("Build failed after {:.1}s", started.elapsed().as_secs_f32())
+        }
+    })
+}
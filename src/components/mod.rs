@@ -2,4 +2,5 @@ pub mod table;
 mod relative_time;
 
 pub use table::Table;
-pub use relative_time::RelativeTime;
\ No newline at end of file
+pub use relative_time::RelativeTime;
+pub use relative_time::format_elapsed;
\ No newline at end of file
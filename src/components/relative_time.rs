@@ -1,13 +1,34 @@
+use std::time::Duration;
 use std::time::Instant;
 
 use vizia::prelude::*;
 
-use crate::AppState;
-
 const SECS_PER_MINUTE: u64 = 60u64 - 1u64;
 const SECS_PER_HOUR: u64 = 60u64 * 60u64 - 1u64;
 const SECS_PER_DAY: u64 = 60u64 * 60u64 * 24u64 - 1u64;
 
+/// Formats a duration the way `RelativeTime` shows it, such as `42s` or `1h 05m 00s`.
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let dur = elapsed.as_secs();
+
+    match dur {
+        0..=SECS_PER_MINUTE => format!("{}s", dur % 60),
+        60..=SECS_PER_HOUR => {
+            format!("{}m {:0>2}s", (dur / 60) % 60, dur % 60)
+        }
+        3600..=SECS_PER_DAY => {
+            format!("{}h {:0>2}m {:0>2}s", (dur / 3600) % 24, (dur / 60) % 60, dur % 60)
+        }
+        _ => format!(
+            "{}d {:0>2}h {:0>2}m {:0>2}s",
+            (dur / 86400),
+            (dur / 3600) % 24,
+            (dur / 60) % 60,
+            dur % 60
+        ),
+    }
+}
+
 pub struct RelativeTime;
 impl View for RelativeTime {
     fn element(&self) -> Option<&'static str> {
@@ -19,31 +40,32 @@ impl RelativeTime {
         Self.build(cx, |cx| {
             Label::new(
                 cx,
-                AppState::started.map(|started| {
-                    if let Some(started) = started {
-                        let dur = started.elapsed().as_secs();
-
-                        match dur {
-                            0..=SECS_PER_MINUTE => format!("{}s", dur % 60),
-                            60..=SECS_PER_HOUR => {
-                                format!("{}m {:0>2}s", (dur / 60) % 60, dur % 60)
-                            }
-                            3600..=SECS_PER_DAY => {
-                                format!("{}h {:0>2}m {:0>2}s", (dur / 3600) % 24, (dur / 60) % 60, dur % 60)
-                            }
-                            _ => format!(
-                                "{}d {:0>2}h {:0>2}m {:0>2}s",
-                                (dur / 62400),
-                                (dur / 3600) % 24,
-                                (dur / 60) % 60,
-                                dur % 60
-                            ),
-                        }
-                    } else {
-                        "Not started".to_owned()
-                    }
+                lens.map(|started| match started {
+                    Some(started) => format_elapsed(started.elapsed()),
+                    None => "Not started".to_owned()
                 }),
             );
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::components::format_elapsed;
+
+    #[test]
+    pub fn formats_elapsed_time() {
+        let format = |secs| format_elapsed(Duration::from_secs(secs));
+
+        assert_eq!(format(0), "0s");
+        assert_eq!(format(59), "59s");
+        assert_eq!(format(60), "1m 00s");
+        assert_eq!(format(3599), "59m 59s");
+        assert_eq!(format(3600), "1h 00m 00s");
+        assert_eq!(format(86399), "23h 59m 59s");
+        assert_eq!(format(86400), "1d 00h 00m 00s");
+        assert_eq!(format(3 * 86400 + 3661), "3d 01h 01m 01s");
+    }
+}
@@ -1 +1,80 @@
-use vizia::prelude::*;
\ No newline at end of file
+use std::time::Duration;
+
+use vizia::prelude::*;
+
+/// Holds the content of one column of a `TableRow`, sized to match the column's heading.
+pub struct TableCell;
+
+impl View for TableCell {
+    fn element(&self) -> Option<&'static str> {
+        Some("table-cell")
+    }
+}
+
+impl TableCell {
+    pub fn new<Content: FnOnce(&mut Context)>(cx: &mut Context, content: Content) -> Handle<Self> {
+        View::build(Self, cx, content)
+            .role(Role::Cell)
+    }
+}
+
+/// What a cell is sorted by, so that a column of durations sorts by length rather than alphabetically. Empty cells
+/// sort first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SortKey {
+    #[default]
+    None,
+    Integer(i64),
+    Duration(Duration),
+    Text(String)
+}
+
+impl From<i64> for SortKey {
+    fn from(value: i64) -> Self {
+        SortKey::Integer(value)
+    }
+}
+
+impl From<Duration> for SortKey {
+    fn from(value: Duration) -> Self {
+        SortKey::Duration(value)
+    }
+}
+
+impl From<String> for SortKey {
+    fn from(value: String) -> Self {
+        SortKey::Text(value)
+    }
+}
+
+impl From<&str> for SortKey {
+    fn from(value: &str) -> Self {
+        SortKey::Text(value.to_owned())
+    }
+}
+
+impl<T: Into<SortKey>> From<Option<T>> for SortKey {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(SortKey::None, Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::components::table::*;
+
+    #[test]
+    pub fn orders_sort_keys() {
+        // Empty cells come first, whatever the other cells hold
+        assert!(SortKey::from(None::<i64>) < SortKey::from(i64::MIN));
+        assert!(SortKey::from(None::<&str>) < SortKey::from(""));
+        assert!(SortKey::from(None::<Duration>) < SortKey::from(Duration::ZERO));
+
+        // Durations compare by length, where their text wouldn't
+        assert!(SortKey::from(Duration::from_secs(9)) < SortKey::from(Duration::from_secs(10)));
+        assert!(SortKey::from(Duration::from_millis(1500)) < SortKey::from(Duration::from_secs(2)));
+        assert!(SortKey::from(-1i64) < SortKey::from(2i64));
+    }
+}
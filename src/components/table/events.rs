@@ -1,15 +1,37 @@
 use vizia::prelude::*;
 use crate::components::table::*;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
 pub enum SortDirection {
     Ascending,
     Descending
 }
 
+/// One of the keys a table is sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub struct SortPreference {
+    /// The `sort_index` of the column.
+    pub column: usize,
+    pub direction: SortDirection
+}
+
 pub enum TableEvents {
-    AlterSource { source: &'static [TableRowBuilder] },
     ResizeColumn { column: usize, size: Units },
-    SetSortPreference { column: usize, sort_direction: SortDirection }
+    /// Sorts by `column` (its `sort_index`) in `sort_direction`, as the only key or, with `append`, after the keys
+    /// already in use.
+    SetSortPreference { column: usize, sort_direction: SortDirection, append: bool },
+    /// What clicking a heading does: sorts by the column, ascending at first and flipping direction after that.
+    ToggleSort { column: usize, append: bool },
+    /// Moves the column at `column` to `index`, shifting the ones in between over.
+    MoveColumn { column: usize, index: usize },
+    SetColumnVisible { column: usize, visible: bool },
+    /// The heading of the column at `column` has been grabbed.
+    DragColumn { column: usize },
+    /// The heading being dragged has been let go over the one at `index`.
+    DropColumn { index: usize },
+    EndDrag,
+    /// The rows have been sorted again, and now show the items at the indices in `order`.
+    Reorder { order: Vec<usize> }
 }
 
 impl View for Table {
@@ -18,11 +40,198 @@ impl View for Table {
     } 
     
     fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
-        event.map(|e, _| match e {
-            None => {},
-            Some(TableEvents::AlterSource { source }) => {},
-            Some(TableEvents::ResizeColumn { column, size }) => {},
-            Some(TableEvents::SetSortPreference { column, sort_direction }) => {},
+        // Letting go anywhere else ends a drag without moving anything. This arrives after any `DropColumn` the same
+        // mouse button sent, since that was emitted on the way up to here.
+        event.map(|window_event: &WindowEvent, _| if let WindowEvent::MouseUp(MouseButton::Left) = window_event {
+            if self.dragging.is_some() {
+                cx.emit(TableEvents::EndDrag);
+            }
+        });
+
+        event.map(|table_event: &TableEvents, meta| match table_event {
+            TableEvents::ResizeColumn { column, size } => {
+                if let Some(column) = self.columns.get_mut(*column) {
+                    column.width = match size {
+                        Units::Pixels(width) => Units::Pixels(width.max(MIN_COLUMN_WIDTH)),
+                        size => *size
+                    };
+                }
+
+                meta.consume();
+            },
+            TableEvents::SetSortPreference { column, sort_direction, append } => {
+                self.sort_by(*column, *sort_direction, *append);
+                meta.consume();
+            },
+            TableEvents::ToggleSort { column, append } => {
+                self.toggle_sort(*column, *append);
+                meta.consume();
+            },
+            TableEvents::MoveColumn { column, index } => {
+                self.move_column(*column, *index);
+                meta.consume();
+            },
+            TableEvents::SetColumnVisible { column, visible } => {
+                self.set_column_visible(*column, *visible);
+                meta.consume();
+            },
+            TableEvents::DragColumn { column } => {
+                self.dragging = Some(*column);
+                meta.consume();
+            },
+            TableEvents::DropColumn { index } => {
+                if let Some(column) = self.dragging.take() {
+                    cx.emit(TableEvents::MoveColumn { column, index: *index });
+                }
+
+                meta.consume();
+            },
+            TableEvents::EndDrag => {
+                self.dragging = None;
+                meta.consume();
+            },
+            TableEvents::Reorder { order } => {
+                self.order = order.clone();
+                meta.consume();
+            }
         });
     }
-}
\ No newline at end of file
+}
+
+impl Table {
+    fn sort_by(&mut self, column: usize, sort_direction: SortDirection, append: bool) {
+        if !append {
+            self.sorting.clear();
+        }
+
+        match self.sorting.iter_mut().find(|i| i.column == column) {
+            Some(preference) => preference.direction = sort_direction,
+            None => self.sorting.push(SortPreference { column, direction: sort_direction })
+        }
+    }
+
+    fn toggle_sort(&mut self, column: usize, append: bool) {
+        let sort_direction = match self.sorting.iter().find(|i| i.column == column) {
+            Some(SortPreference { direction: SortDirection::Ascending, .. }) => SortDirection::Descending,
+            _ => SortDirection::Ascending
+        };
+
+        self.sort_by(column, sort_direction, append);
+    }
+
+    fn move_column(&mut self, column: usize, index: usize) {
+        if column < self.columns.len() && index < self.columns.len() {
+            let moved = self.columns.remove(column);
+            self.columns.insert(index, moved);
+        }
+    }
+
+    fn set_column_visible(&mut self, column: usize, visible: bool) {
+        // Hiding every column would leave no heading to right-click to bring them back
+        let shown = self.columns.iter().filter(|i| i.visible).count();
+        if let Some(info) = self.columns.get_mut(column).filter(|i| visible || !i.visible || shown > 1) {
+            info.visible = visible;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::table::*;
+
+    fn table(headings: &[&str]) -> Table {
+        Table {
+            columns: headings.iter()
+                .enumerate()
+                .map(|(a, i)| TableColumnInfo { heading: i.to_string(), sort_index: a, ..Default::default() })
+                .collect(),
+            sorting: vec![],
+            order: vec![],
+            dragging: None
+        }
+    }
+
+    fn preference(column: usize, direction: SortDirection) -> SortPreference {
+        SortPreference { column, direction }
+    }
+
+    #[test]
+    pub fn sorts_by_several_keys() {
+        use SortDirection::*;
+
+        let mut table = table(&["Status", "Job"]);
+        table.sort_by(0, Ascending, false);
+        assert_eq!(table.sorting, [preference(0, Ascending)]);
+
+        table.sort_by(1, Descending, true);
+        assert_eq!(table.sorting, [preference(0, Ascending), preference(1, Descending)]);
+
+        // A key already in use keeps its place, only changing direction
+        table.sort_by(0, Descending, true);
+        assert_eq!(table.sorting, [preference(0, Descending), preference(1, Descending)]);
+
+        table.sort_by(1, Ascending, false);
+        assert_eq!(table.sorting, [preference(1, Ascending)]);
+    }
+
+    #[test]
+    pub fn toggles_sort_direction() {
+        use SortDirection::*;
+
+        let mut table = table(&["Status", "Job"]);
+        table.toggle_sort(0, false);
+        assert_eq!(table.sorting, [preference(0, Ascending)]);
+        table.toggle_sort(0, false);
+        assert_eq!(table.sorting, [preference(0, Descending)]);
+        table.toggle_sort(0, false);
+        assert_eq!(table.sorting, [preference(0, Ascending)]);
+
+        table.toggle_sort(1, true);
+        assert_eq!(table.sorting, [preference(0, Ascending), preference(1, Ascending)]);
+        table.toggle_sort(1, true);
+        assert_eq!(table.sorting, [preference(0, Ascending), preference(1, Descending)]);
+
+        // Without shift, the column becomes the only key, still flipping from the direction it had
+        table.toggle_sort(1, false);
+        assert_eq!(table.sorting, [preference(1, Ascending)]);
+    }
+
+    #[test]
+    pub fn moves_columns() {
+        let headings = |table: &Table| table.columns.iter().map(|i| i.heading.clone()).collect::<Vec<_>>();
+
+        let mut table = table(&["Status", "Job", "Elapsed"]);
+        table.move_column(0, 2);
+        assert_eq!(headings(&table), ["Job", "Elapsed", "Status"]);
+        table.move_column(2, 0);
+        assert_eq!(headings(&table), ["Status", "Job", "Elapsed"]);
+
+        // Anything out of range leaves the columns as they are
+        table.move_column(3, 0);
+        table.move_column(0, 3);
+        assert_eq!(headings(&table), ["Status", "Job", "Elapsed"]);
+    }
+
+    #[test]
+    pub fn keeps_one_column_visible() {
+        let visible = |table: &Table| table.columns.iter().map(|i| i.visible).collect::<Vec<_>>();
+
+        let mut table = table(&["Status", "Job"]);
+        table.set_column_visible(0, false);
+        assert_eq!(visible(&table), [false, true]);
+
+        // The last column shown stays, even when asked twice
+        table.set_column_visible(1, false);
+        table.set_column_visible(1, false);
+        assert_eq!(visible(&table), [false, true]);
+
+        // Hiding an already hidden column, or one that doesn't exist, changes nothing either
+        table.set_column_visible(0, false);
+        table.set_column_visible(2, false);
+        assert_eq!(visible(&table), [false, true]);
+
+        table.set_column_visible(0, true);
+        table.set_column_visible(1, false);
+        assert_eq!(visible(&table), [true, false]);
+    }
+}
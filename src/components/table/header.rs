@@ -1,19 +1,169 @@
+use vizia::icons::ICON_CHEVRON_DOWN;
+use vizia::icons::ICON_CHEVRON_UP;
 use vizia::prelude::*;
 
 use crate::components::table::*;
 
-pub struct TableHeader {
-    columns: Vec<TableColumnInfo>
-}
+/// The row of headings at the top of a `Table`. It's only rebuilt when the columns shown change, so that resizing a
+/// column doesn't pull the splitter out from under the mouse. Right-clicking it opens a menu of every column, to hide
+/// or show them.
+pub struct TableHeader;
 
 impl View for TableHeader {
     fn element(&self) -> Option<&'static str> {
         Some("table-header")
     }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event: &WindowEvent, meta| if let WindowEvent::MouseDown(MouseButton::Right) = window_event {
+            cx.emit(PopupEvent::Open);
+            meta.consume();
+        });
+    }
 }
 
 impl TableHeader {
-    pub(crate) fn new<'a>(cx: &'a mut Context, columns: Vec<TableColumnInfo>) -> Handle<'a, Self> {
-        View::build(Self { columns }, cx, |cx| {})
+    pub(crate) fn new<Columns: 'static + Lens<Target=Vec<TableColumnInfo>>>(cx: &mut Context, columns: Columns) -> Handle<Self> {
+        View::build(Self, cx, move |cx| {
+            PopupData::default().build(cx);
+
+            let headings = columns.clone().map(|i| visible_headings(i));
+            let shown = columns.clone();
+            Binding::new(cx, headings, move |cx, _| {
+                for (index, column) in shown.get(cx).into_iter().enumerate().filter(|(_, i)| i.visible) {
+                    TableHeaderColumn::new(cx, index, column.sort_index, column.heading)
+                        .width(shown.clone().map(move |i| i.get(index).map_or(Units::Stretch(1.0), |i| i.width)));
+                }
+            });
+
+            Popup::new(cx, PopupData::is_open, true, move |cx| {
+                let columns = columns.clone();
+                Binding::new(cx, columns.clone().map(|i| i.len()), move |cx, len| {
+                    for index in 0..len.get(cx) {
+                        let column = columns.clone().index(index);
+                        HStack::new(cx, |cx| {
+                            Checkbox::new(cx, column.clone().map(|i| i.visible));
+                            Label::new(cx, column.clone().map(|i| i.heading.clone()));
+                        })
+                            .class("table-column-toggle")
+                            .on_press(move |cx| {
+                                let visible = column.get(cx).visible;
+                                cx.emit(TableEvents::SetColumnVisible { column: index, visible: !visible });
+                            });
+                    }
+                });
+            })
+                .on_blur(|cx| cx.emit(PopupEvent::Close))
+                .class("table-column-menu");
+        })
+            .role(Role::Row)
+            .layout_type(LayoutType::Row)
+    }
+}
+
+#[derive(Lens)]
+pub struct TableHeaderColumn {
+    heading: String,
+    index: usize
+}
+
+impl View for TableHeaderColumn {
+    fn element(&self) -> Option<&'static str> {
+        Some("table-header-column")
+    }
+
+    /// Dragging a heading onto another moves its column there. The mouse isn't captured, so that letting go is seen by
+    /// the heading it's let go over.
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event: &WindowEvent, _| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => cx.emit(TableEvents::DragColumn { column: self.index }),
+            WindowEvent::MouseUp(MouseButton::Left) => cx.emit(TableEvents::DropColumn { index: self.index }),
+            _ => {}
+        });
+    }
+}
+
+impl TableHeaderColumn {
+    /// `index` is where the column is in the table, and `sort_index` which column it is as far as sorting goes.
+    /// Clicking the heading sorts by the column, and shift-clicking adds it to the columns already sorted by.
+    pub fn new(cx: &mut Context, index: usize, sort_index: usize, heading: String) -> Handle<Self> {
+        View::build(Self { heading, index }, cx, move |cx| {
+            Label::new(cx, Self::heading)
+                .width(Units::Stretch(1.0))
+                .left(Units::Pixels(2.0))
+                .right(Units::Pixels(2.0));
+
+            // Which way the column is sorted, and when there's more than one key, where it comes among them
+            Label::new(cx, Table::sorting.map(move |sorting| {
+                let Some(position) = sorting.iter().position(|i| i.column == sort_index) else { return String::new() };
+                let chevron = match sorting[position].direction {
+                    SortDirection::Ascending => ICON_CHEVRON_UP,
+                    SortDirection::Descending => ICON_CHEVRON_DOWN
+                };
+
+                match sorting.len() {
+                    1 => chevron.to_owned(),
+                    _ => format!("{}{}", chevron, position + 1)
+                }
+            }))
+                .class("table-sort-indicator")
+                .left(Units::Pixels(2.0))
+                .right(Units::Pixels(2.0));
+
+            let column = cx.current();
+            Splitter::new(cx, column, move |cx, width| cx.emit(TableEvents::ResizeColumn { column: index, size: Units::Pixels(width) }));
+        })
+            .layout_type(LayoutType::Row)
+            .toggle_class("dragging", Table::dragging.map(move |i| *i == Some(index)))
+            .on_press(move |cx| {
+                let append = cx.modifiers().contains(Modifiers::SHIFT);
+                cx.emit(TableEvents::ToggleSort { column: sort_index, append });
+            })
+    }
+}
+
+/// The handle on the right edge of a heading, which resizes its column when dragged.
+struct Splitter<OnResize: 'static + Fn(&mut EventContext, f32)> {
+    /// The heading being resized. Its left edge stays put, so the new width is the distance from there to the mouse.
+    column: Entity,
+    is_dragging: bool,
+    on_resize: OnResize
+}
+
+impl<OnResize: 'static + Fn(&mut EventContext, f32)> View for Splitter<OnResize> {
+    fn element(&self) -> Option<&'static str> {
+        Some("table-splitter")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event: &WindowEvent, meta| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                self.is_dragging = true;
+                cx.capture();
+                cx.lock_cursor_icon();
+                meta.consume();
+            },
+            WindowEvent::MouseMove(x, _) if self.is_dragging => {
+                let left = cx.with_current(self.column, |cx| cx.bounds().x);
+
+                // Bounds and mouse positions are in physical pixels, but widths are set in logical ones
+                (self.on_resize)(cx, (*x - left) / cx.scale_factor());
+            },
+            WindowEvent::MouseUp(MouseButton::Left) if self.is_dragging => {
+                self.is_dragging = false;
+                cx.release();
+                cx.unlock_cursor_icon();
+                meta.consume();
+            },
+            // Finishing a drag shouldn't count as clicking the heading
+            WindowEvent::PressDown { .. } | WindowEvent::Press { .. } => meta.consume(),
+            _ => {}
+        });
     }
-}
\ No newline at end of file
+}
+
+impl<OnResize: 'static + Fn(&mut EventContext, f32)> Splitter<OnResize> {
+    fn new(cx: &mut Context, column: Entity, on_resize: OnResize) -> Handle<Self> {
+        View::build(Self { column, is_dragging: false, on_resize }, cx, |_| {})
+    }
+}
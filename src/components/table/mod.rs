@@ -1,4 +1,5 @@
-use std::collections::VecDeque;
+use std::cmp::Ordering;
+use std::rc::Rc;
 
 use vizia::prelude::*;
 
@@ -9,14 +10,21 @@ mod header;
 
 pub use row::*;
 pub use cell::*;
+pub use events::*;
 
 use self::header::TableHeader;
 
+/// How narrow a column can be dragged, in logical pixels.
+pub const MIN_COLUMN_WIDTH: f32 = 24.0;
+
+/// A column of a `Table`. Columns are kept in the order they're shown in, hidden ones included.
 #[derive(Clone, Data)]
 pub struct TableColumnInfo {
     width: Units,
     heading: String,
-    sort_index: usize
+    /// Which column this is, however the columns have been moved around since.
+    sort_index: usize,
+    visible: bool
 }
 
 impl Default for TableColumnInfo {
@@ -24,47 +32,179 @@ impl Default for TableColumnInfo {
         Self {
             width: Units::Stretch(1.0),
             heading: "".to_owned(),
-            sort_index: 0
+            sort_index: 0,
+            visible: true
         }
     }
 }
 
+/// The headings of the columns being shown, in order.
+fn visible_headings(columns: &[TableColumnInfo]) -> Vec<String> {
+    columns.iter()
+        .filter(|i| i.visible)
+        .map(|i| i.heading.clone())
+        .collect()
+}
+
 #[derive(Lens)]
 pub struct Table {
     columns: Vec<TableColumnInfo>,
-    body: Vec<TableRowBuilder>,
-    sorting: VecDeque<usize>
+    /// The keys rows are sorted by, most significant first.
+    sorting: Vec<SortPreference>,
+    /// The index of each row's item, in the order the rows are shown.
+    order: Vec<usize>,
+    /// Where the column whose heading is being dragged is.
+    dragging: Option<usize>
+}
+
+/// Lets a `Table` start out with some of its columns hidden.
+pub trait TableModifiers {
+    fn hide_columns<'a>(self, headings: impl IntoIterator<Item=&'a str>) -> Self;
+}
+
+impl TableModifiers for Handle<'_, Table> {
+    fn hide_columns<'a>(self, headings: impl IntoIterator<Item=&'a str>) -> Self {
+        let headings = headings.into_iter().collect::<Vec<_>>();
+
+        self.modify(|table| for column in &mut table.columns {
+            if headings.contains(&column.heading.as_str()) {
+                column.visible = false;
+            }
+        })
+    }
 }
 
 impl Table {
-    pub fn new<Headings, Body>(cx: &mut Context, headings: Headings, body: Body) -> Handle<Self>
+    /// Shows a row for each item of `rows`, with the cells `row` describes for the item at each index. Rows are only
+    /// rebuilt when the number of items, the order they're sorted into or the columns shown change, so anything inside
+    /// them that changes more often should bind to the item itself. `keys` picks out whatever part of an item its sort
+    /// keys depend on, and the rows are only sorted again when that changes.
+    pub fn new<Headings, Rows, Item, Keys, Key, Row>(cx: &mut Context, headings: Headings, rows: Rows, keys: Keys, row: Row) -> Handle<Self>
     where 
-        Body: 'static + IntoIterator<Item=TableRowBuilder>,
-        Headings: 'static + IntoIterator<Item=&'static str> {
+        Headings: 'static + IntoIterator<Item=&'static str>,
+        Rows: 'static + Lens<Target=Vec<Item>>,
+        Item: 'static,
+        Keys: 'static + Fn(&Item) -> Key,
+        Key: 'static + Data,
+        Row: 'static + Fn(&mut Context, usize) -> TableRowBuilder {
         View::build(Self { 
-            body: vec![],
-            sorting: VecDeque::new(),
+            sorting: vec![],
+            order: vec![],
+            dragging: None,
             columns: headings.into_iter()
                 .enumerate()
                 .map(|(a, i)| TableColumnInfo {
                     heading: i.to_owned(),
                     sort_index: a,
-                    width: Units::Stretch(1.0)
+                    ..Default::default()
                 })
                 .collect()
-        }, cx, |cx| {
-            Binding::new(cx, Table::columns, |cx, item| {
-                let cols = item.get(cx);
-                TableHeader::new(cx, cols);
-                
-                ScrollView::new(cx, 0.0, 0.0, false, true, |cx| {
-                    Binding::new(cx, Table::body, |cx, body| {
-                        for i in body.get(cx) {
-                            i
-                        }
-                    })
-                })
+        }, cx, move |cx| {
+            TableHeader::new(cx, Table::columns);
+
+            let row = Rc::new(row);
+
+            // Only `row` knows the keys, so they're worked out again whenever what they depend on changes, and the rows
+            // rebuilt if that moves any of them
+            let (keys_row, keys_rows) = (row.clone(), rows.clone());
+            let keys = Rc::new(keys);
+            Binding::new(cx, Table::sorting, move |cx, _| {
+                let (row, keys) = (keys_row.clone(), keys.clone());
+                Binding::new(cx, keys_rows.clone().map(move |i| i.iter().map(&*keys).collect::<Vec<_>>()), move |cx, _| {
+                    let builders = sorted_rows(cx, &*row, keys_rows.clone());
+                    let order = builders.iter().map(|i| i.0).collect();
+                    cx.emit(TableEvents::Reorder { order });
+                });
             });
+
+            ScrollView::new(cx, 0.0, 0.0, false, true, move |cx| {
+                Binding::new(cx, Table::columns.map(|i| visible_headings(i)), move |cx, _| {
+                    let (row, rows) = (row.clone(), rows.clone());
+                    Binding::new(cx, Table::order, move |cx, _| {
+                        let row = row.clone();
+                        Binding::new(cx, rows.clone().map(|i| i.len()), move |cx, _| {
+                            for (position, (_, builder)) in sorted_rows(cx, &*row, rows.clone()).into_iter().enumerate() {
+                                builder.into_row(cx, Table::columns)
+                                    .toggle_class("odd", position % 2 == 1);
+                            }
+                        });
+                    });
+                });
+            })
+            .class("table-contents");
         })
     }
-}
\ No newline at end of file
+}
+
+/// Describes a row for each item of `rows`, sorted the way the table currently is, along with the index of its item.
+fn sorted_rows<Rows, Item>(cx: &mut Context, row: &dyn Fn(&mut Context, usize) -> TableRowBuilder, rows: Rows) -> Vec<(usize, TableRowBuilder)>
+where
+    Rows: 'static + Lens<Target=Vec<Item>>,
+    Item: 'static {
+    let columns = Table::columns.get(cx);
+    let sorting = Table::sorting.get(cx);
+
+    let mut builders = (0..rows.map(|i| i.len()).get(cx))
+        .map(|index| (index, row(cx, index)))
+        .collect::<Vec<_>>();
+    builders.sort_by(|a, b| compare(&a.1, &b.1, &columns, &sorting));
+    builders
+}
+
+/// Orders two rows by each of the `sorting` keys in turn. Rows which compare equal keep their order, since the sort is
+/// stable.
+fn compare(a: &TableRowBuilder, b: &TableRowBuilder, columns: &[TableColumnInfo], sorting: &[SortPreference]) -> Ordering {
+    sorting.iter()
+        .filter_map(|preference| {
+            let column = columns.iter().find(|i| i.sort_index == preference.column)?;
+            let ordering = a.key(&column.heading).cmp(b.key(&column.heading));
+
+            Some(match preference.direction {
+                SortDirection::Ascending => ordering,
+                SortDirection::Descending => ordering.reverse()
+            })
+        })
+        .find(|i| i.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use crate::components::table::*;
+
+    use super::compare;
+
+    fn row(status: i64, job: &str) -> TableRowBuilder {
+        TableRowBuilder::new()
+            .add_sorted_column("Status", status, |_| {})
+            .add_sorted_column("Job", job, |_| {})
+    }
+
+    #[test]
+    pub fn compares_by_each_key_in_turn() {
+        use SortDirection::*;
+
+        let columns = ["Status", "Job"].iter()
+            .enumerate()
+            .map(|(a, i)| TableColumnInfo { heading: i.to_string(), sort_index: a, ..Default::default() })
+            .collect::<Vec<_>>();
+        let rows = [row(1, "b"), row(1, "a"), row(0, "c")];
+        let order = |sorting: &[SortPreference]| {
+            let mut order = (0..rows.len()).collect::<Vec<_>>();
+            order.sort_by(|a, b| compare(&rows[*a], &rows[*b], &columns, sorting));
+            order
+        };
+
+        assert_eq!(order(&[]), [0, 1, 2]);
+        assert_eq!(order(&[SortPreference { column: 0, direction: Ascending }]), [2, 0, 1]);
+        assert_eq!(order(&[SortPreference { column: 0, direction: Ascending }, SortPreference { column: 1, direction: Ascending }]), [2, 1, 0]);
+        assert_eq!(order(&[SortPreference { column: 0, direction: Descending }, SortPreference { column: 1, direction: Ascending }]), [1, 0, 2]);
+        assert_eq!(order(&[SortPreference { column: 0, direction: Ascending }, SortPreference { column: 1, direction: Descending }]), [2, 0, 1]);
+
+        // Keys follow the column, wherever it's been moved to
+        let moved = [columns[1].clone(), columns[0].clone()];
+        assert_eq!(compare(&rows[0], &rows[1], &moved, &[SortPreference { column: 1, direction: Ascending }]), Ordering::Greater);
+    }
+}
@@ -2,33 +2,103 @@ use vizia::prelude::*;
 
 use super::*;
 
-#[derive(Lens)]
-pub struct TableRow<Columns: 'static + Lens<Target=Vec<TableColumnInfo>>> {
-    columns: Columns
-}
+pub struct TableRow;
 
-impl<Columns: 'static + Lens<Target=Vec<TableColumnInfo>>> View for TableRow<Columns> {
+impl View for TableRow {
     fn element(&self) -> Option<&'static str> {
         Some("table-row")
     }
 }
 
-impl<Columns: 'static + Lens<Target=Vec<TableColumnInfo>>> TableRow<Columns> {
-    pub fn new(cx: &mut Context, columns: Columns) -> Handle<Self> {
-        View::build(Self { columns }, cx, |cx| {})
+impl TableRow {
+    pub fn new<Content: FnOnce(&mut Context)>(cx: &mut Context, content: Content) -> Handle<Self> {
+        View::build(Self, cx, content)
+            .role(Role::Row)
+            .layout_type(LayoutType::Row)
     }
 }
 
-pub struct TableRowBuilder {}
+struct CellBuilder {
+    column: String,
+    key: SortKey,
+    body: Box<dyn FnOnce(&mut Context)>
+}
+
+type RowModifier = Box<dyn FnOnce(Handle<'_, TableRow>) -> Handle<'_, TableRow>>;
+
+fn row_modifier<F>(modifier: F) -> RowModifier
+where
+    F: 'static + for<'a> FnOnce(Handle<'a, TableRow>) -> Handle<'a, TableRow> {
+    Box::new(modifier)
+}
+
+/// Describes the cells of a row, by the heading of the column each one belongs in.
+#[derive(Default)]
+pub struct TableRowBuilder {
+    cells: Vec<CellBuilder>,
+    /// Applied to the row once it's built, since the rows themselves are made by the table.
+    modifiers: Vec<RowModifier>
+}
 
 impl TableRowBuilder {
-    pub fn new() -> Self { Self {} }
+    pub fn new() -> Self { Self::default() }
     
-    pub fn add_column<Body: FnMut(&mut Context)>(self, column: &str, body: Body) -> Self {
+    pub fn add_column<Body: 'static + FnOnce(&mut Context)>(self, column: &str, body: Body) -> Self {
+        self.add_sorted_column(column, SortKey::None, body)
+    }
+
+    /// Adds a cell which is sorted by `key` rather than being left where it is.
+    pub fn add_sorted_column<Key, Body>(mut self, column: &str, key: Key, body: Body) -> Self
+    where
+        Key: Into<SortKey>,
+        Body: 'static + FnOnce(&mut Context) {
+        self.cells.push(CellBuilder { column: column.to_owned(), key: key.into(), body: Box::new(body) });
+        self
+    }
+
+    /// Runs `action` when the row is clicked.
+    pub fn on_press<F: 'static + Fn(&mut EventContext)>(mut self, action: F) -> Self {
+        self.modifiers.push(row_modifier(move |row| row.on_press(action)));
+        self
+    }
+
+    /// Gives the row the class `name` while `lens` is true, for showing which row is selected, say.
+    pub fn toggle_class<L: 'static + Lens<Target=bool>>(mut self, name: &'static str, lens: L) -> Self {
+        self.modifiers.push(row_modifier(move |row| row.toggle_class(name, lens)));
         self
     }
+
+    /// The sort key of the cell in `column`, or `SortKey::None` if the row has no such cell.
+    pub fn key(&self, column: &str) -> &SortKey {
+        const NONE: &SortKey = &SortKey::None;
+
+        self.cells.iter()
+            .find(|i| i.column == column)
+            .map_or(NONE, |i| &i.key)
+    }
     
-    pub(crate) fn into_row<Columns: 'static + Lens<Target=Vec<TableColumnInfo>>>(self, cx: &mut Context, columns: Columns) -> Handle<TableRow<Columns>> {
-        TableRow::new(cx, columns)
+    /// Builds a `TableCell` for every visible column, in the order of `columns`, each as wide as its column. Columns
+    /// this row has no content for get an empty cell, and content for columns that are hidden or that the table
+    /// doesn't have is left out.
+    pub(crate) fn into_row<Columns: 'static + Lens<Target=Vec<TableColumnInfo>>>(self, cx: &mut Context, columns: Columns) -> Handle<TableRow> {
+        let headings = visible_headings(&columns.get(cx));
+        let mut cells = self.cells;
+
+        let row = TableRow::new(cx, move |cx| {
+            for heading in headings {
+                let body = cells.iter()
+                    .position(|i| i.column == heading)
+                    .map(|i| cells.remove(i).body);
+
+                TableCell::new(cx, |cx| if let Some(body) = body {
+                    body(cx);
+                })
+                    .width(columns.clone().map(move |i| i.iter()
+                        .find(|i| i.heading == heading)
+                        .map_or(Units::Stretch(1.0), |i| i.width)));
+            }
+        });
+
+        self.modifiers.into_iter().fold(row, |row, modifier| modifier(row))
     }
-}
\ No newline at end of file
+}
@@ -1,18 +1,341 @@
+use std::path::PathBuf;
+
+use vizia::icons::*;
 use vizia::prelude::*;
 
+use crate::AppEvent;
+use crate::AppState;
+use crate::build::*;
+
+mod tree;
+
+pub use tree::*;
+
+#[derive(Debug, Clone)]
+pub enum EditEvent {
+    Rename(String),
+    SetImageSize(Option<u64>),
+    AddPartition,
+    RemovePartition(usize),
+    MovePartition { from: usize, to: usize },
+    SetLabel(usize, String),
+    SetPreset(usize, PresetKind),
+    SetOffset(usize, Option<u64>),
+    SetSize(usize, Option<u64>),
+    AddNode { partition: usize, path: PathBuf },
+    RemoveNode { partition: usize, node: usize },
+    SetNodePath { partition: usize, node: usize, path: PathBuf },
+    SetNodeSource { partition: usize, node: usize, source: NodeSource },
+    AddDependency { partition: usize, node: usize, dependency: PathBuf },
+    RemoveDependency { partition: usize, node: usize, dependency: PathBuf },
+}
+
+impl EditEvent {
+    /// Applies the edit to `config`. Edits referring to partitions which don't exist are ignored.
+    pub fn apply(&self, config: &mut BuildConfig) {
+        match self {
+            EditEvent::Rename(name) => config.name = name.clone(),
+            EditEvent::SetImageSize(size) => config.image_size = *size,
+            EditEvent::AddPartition => config.partitions.push(Partition {
+                label: format!("partition{}", config.partitions.len() + 1),
+                purpose: PartitionPreset::Raw { source: None, fill: None },
+                offset: None,
+                size: None
+            }),
+            EditEvent::RemovePartition(index) => if *index < config.partitions.len() {
+                config.partitions.remove(*index);
+            },
+            EditEvent::MovePartition { from, to } => if *from < config.partitions.len() && *to < config.partitions.len() {
+                let partition = config.partitions.remove(*from);
+                config.partitions.insert(*to, partition);
+            },
+            EditEvent::SetLabel(index, label) => if let Some(partition) = config.partitions.get_mut(*index) {
+                partition.label = label.clone();
+            },
+            EditEvent::SetPreset(index, kind) => if let Some(partition) = config.partitions.get_mut(*index) {
+                partition.purpose = partition.purpose.with_kind(*kind);
+            },
+            EditEvent::SetOffset(index, offset) => if let Some(partition) = config.partitions.get_mut(*index) {
+                partition.offset = *offset;
+            },
+            EditEvent::SetSize(index, size) => if let Some(partition) = config.partitions.get_mut(*index) {
+                partition.size = *size;
+            },
+            EditEvent::AddNode { partition, path } => if let Some(filesystem) = filesystem(config, *partition) {
+                filesystem.push(FSNode {
+                    path: path.clone(),
+                    source: NodeSource::Static { location: String::new(), sha256: None },
+                    dependencies: None
+                });
+            },
+            EditEvent::RemoveNode { partition, node } => if let Some(filesystem) = filesystem(config, *partition) {
+                if *node < filesystem.len() {
+                    filesystem.remove(*node);
+                }
+            },
+            EditEvent::SetNodePath { partition, node, path } => {
+                let Some(old) = filesystem(config, *partition)
+                    .and_then(|i| i.get_mut(*node))
+                    .map(|i| std::mem::replace(&mut i.path, path.clone())) else { return; };
+
+                // Keep dependency links pointing at the node once it's been moved.
+                for dependency in config.partitions.iter_mut()
+                    .filter_map(|i| i.purpose.filesystem_mut())
+                    .flatten()
+                    .filter_map(|i| i.dependencies.as_mut())
+                    .flatten()
+                    .filter(|i| **i == old) {
+                    *dependency = path.clone();
+                }
+            },
+            EditEvent::SetNodeSource { partition, node, source } => if let Some(node) = filesystem(config, *partition).and_then(|i| i.get_mut(*node)) {
+                node.source = source.clone();
+            },
+            EditEvent::AddDependency { partition, node, dependency } => if let Some(node) = filesystem(config, *partition).and_then(|i| i.get_mut(*node)) {
+                let dependencies = node.dependencies.get_or_insert_with(Vec::new);
+                if !dependencies.contains(dependency) {
+                    dependencies.push(dependency.clone());
+                }
+            },
+            EditEvent::RemoveDependency { partition, node, dependency } => if let Some(node) = filesystem(config, *partition).and_then(|i| i.get_mut(*node)) {
+                if let Some(dependencies) = &mut node.dependencies {
+                    dependencies.retain(|i| i != dependency);
+                }
+
+                if node.dependencies.as_ref().is_some_and(Vec::is_empty) {
+                    node.dependencies = None;
+                }
+            },
+        }
+    }
+}
+
+fn filesystem(config: &mut BuildConfig, partition: usize) -> Option<&mut Vec<FSNode>> {
+    config.partitions.get_mut(partition)?.purpose.filesystem_mut()
+}
+
+/// Parses a byte count such as `4096`, `512K` or `16MiB`, using binary multiples. An empty string means "unset".
+pub fn parse_size(text: &str) -> Option<Option<u64>> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Some(None);
+    }
+
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let shift = match unit.trim().trim_end_matches("iB").trim_end_matches('B').to_ascii_uppercase().as_str() {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return None
+    };
+
+    number.parse::<u64>().ok()?
+        .checked_mul(1 << shift)
+        .map(Some)
+}
+
+fn size_field<L, F>(cx: &mut Context, lens: L, on_change: F) -> Handle<Textbox<impl Lens<Target=String>>>
+where
+    L: 'static + Lens<Target=Option<u64>>,
+    F: 'static + Fn(Option<u64>) -> EditEvent + Send + Sync {
+    Textbox::new(cx, lens.map(|i| i.map(|i| i.to_string()).unwrap_or_default()))
+        .on_submit(move |cx, text, _| if let Some(size) = parse_size(&text) {
+            cx.emit(on_change(size));
+        })
+}
+
+pub enum EditorEvent {
+    ShowFiles(Option<usize>)
+}
+
 #[derive(Lens)]
 pub struct Editor {
-    open: bool
+    /// The partition whose filesystem is being edited, if any.
+    files: Option<usize>
 }
 
-impl Editor {
-    pub fn new(cx: &mut Context, config: crate::build::BuildConfig) -> Self {
-        Popup::new(cx, Editor::open, true, |cx| {
-            Label::new(cx, "Hello World");
+impl View for Editor {
+    fn element(&self) -> Option<&'static str> {
+        Some("editor")
+    }
+
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|e, _| match e {
+            EditorEvent::ShowFiles(files) => self.files = *files
         });
-        
-        Self {
-            open: true
-        }
+
+        event.map(|e, _| match e {
+            EditEvent::RemovePartition(_) | EditEvent::MovePartition { .. } => self.files = None,
+            _ => {}
+        });
+    }
+}
+
+impl Editor {
+    pub fn new(cx: &mut Context) -> Handle<Self> {
+        View::build(Self { files: None }, cx, |cx| {
+            Binding::new(cx, Editor::files, |cx, files| {
+                match files.get(cx) {
+                    Some(partition) => Self::show_files(cx, partition),
+                    None => Self::show_partitions(cx)
+                }
+            });
+        })
+    }
+
+    fn show_files(cx: &mut Context, partition: usize) {
+        HStack::new(cx, |cx| {
+            Button::new(cx, |cx| cx.emit(EditorEvent::ShowFiles(None)), |cx| {
+                HStack::new(cx, |cx| {
+                    Label::new(cx, ICON_ARROW_LEFT);
+                    Label::new(cx, "Partitions");
+                })
+            })
+            .class("secondary");
+
+            Label::new(cx, AppState::config.map(move |i| i.as_ref()
+                .and_then(|i| i.partitions.get(partition))
+                .map(|i| i.label.clone())
+                .unwrap_or_default()));
+        })
+        .class("editor-field");
+
+        FileTree::new(cx, partition);
+    }
+
+    fn show_partitions(cx: &mut Context) {
+        HStack::new(cx, |cx| {
+            Label::new(cx, "Name");
+            Textbox::new(cx, AppState::config.map(|i| i.as_ref().map(|i| i.name.clone()).unwrap_or_default()))
+                .on_submit(|cx, text, _| cx.emit(EditEvent::Rename(text)))
+                .width(Stretch(1.0));
+        })
+        .class("editor-field");
+
+        HStack::new(cx, |cx| {
+            Label::new(cx, "Image size");
+            Textbox::new(cx, AppState::config.map(|i| i.as_ref().and_then(|i| i.image_size).map(|i| i.to_string()).unwrap_or_default()))
+                .on_submit(|cx, text, _| if let Some(size) = parse_size(&text) {
+                    cx.emit(EditEvent::SetImageSize(size));
+                })
+                .width(Stretch(1.0));
+        })
+        .class("editor-field");
+
+        HStack::new(cx, |cx| {
+            for heading in ["Label", "Type", "Offset", "Size", ""] {
+                Label::new(cx, heading).width(Stretch(1.0));
+            }
+        })
+        .class("editor-heading");
+
+        ScrollView::new(cx, 0.0, 0.0, false, true, |cx| {
+            Binding::new(cx, AppState::config.map(|i| i.as_ref().map(|i| i.partitions.clone()).unwrap_or_default()), |cx, partitions| {
+                let count = partitions.get(cx).len();
+
+                for (index, partition) in partitions.get(cx).into_iter().enumerate() {
+                    let kind = partition.purpose.kind();
+                    let field = move |i: &Option<BuildConfig>| i.as_ref().and_then(|i| i.partitions.get(index)).cloned();
+
+                    HStack::new(cx, |cx| {
+                        Textbox::new(cx, AppState::config.map(move |i| field(i).map(|i| i.label).unwrap_or_default()))
+                            .on_submit(move |cx, text, _| cx.emit(EditEvent::SetLabel(index, text)))
+                            .width(Stretch(1.0));
+
+                        Dropdown::new(cx, move |cx| {
+                            Label::new(cx, kind.name());
+                        }, move |cx| {
+                            for kind in PresetKind::ALL {
+                                Label::new(cx, kind.name())
+                                    .on_press(move |cx| {
+                                        cx.emit(EditEvent::SetPreset(index, kind));
+                                        cx.emit(PopupEvent::Close);
+                                    });
+                            }
+                        })
+                        .width(Stretch(1.0));
+
+                        size_field(cx, AppState::config.map(move |i| field(i).and_then(|i| i.offset)), move |offset| EditEvent::SetOffset(index, offset))
+                            .width(Stretch(1.0));
+                        size_field(cx, AppState::config.map(move |i| field(i).and_then(|i| i.size)), move |size| EditEvent::SetSize(index, size))
+                            .width(Stretch(1.0));
+
+                        HStack::new(cx, |cx| {
+                            Button::new(cx, move |cx| cx.emit(EditorEvent::ShowFiles(Some(index))), |cx| Label::new(cx, ICON_FOLDER))
+                                .disabled(!kind.has_filesystem());
+                            Button::new(cx, move |cx| cx.emit(EditEvent::MovePartition { from: index, to: index.saturating_sub(1) }), |cx| Label::new(cx, ICON_ARROW_UP))
+                                .disabled(index == 0);
+                            Button::new(cx, move |cx| cx.emit(EditEvent::MovePartition { from: index, to: index + 1 }), |cx| Label::new(cx, ICON_ARROW_DOWN))
+                                .disabled(index + 1 == count);
+                            Button::new(cx, move |cx| cx.emit(EditEvent::RemovePartition(index)), |cx| Label::new(cx, ICON_TRASH))
+                                .class("danger");
+                        })
+                        .width(Stretch(1.0));
+                    })
+                    .class("editor-row");
+                }
+            });
+        })
+        .class("field");
+
+        HStack::new(cx, |cx| {
+            Button::new(cx, |cx| cx.emit(EditEvent::AddPartition), |cx| {
+                HStack::new(cx, |cx| {
+                    Label::new(cx, ICON_PLUS);
+                    Label::new(cx, "Add Partition");
+                })
+            })
+            .class("secondary");
+
+            Element::new(cx).width(Stretch(1.0));
+
+            Button::new(cx, |cx| cx.emit(AppEvent::Edit(false)), |cx| {
+                HStack::new(cx, |cx| {
+                    Label::new(cx, ICON_CHECK);
+                    Label::new(cx, "Done");
+                })
+            })
+            .class("primary");
+        })
+        .height(Auto);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::build::*;
+    use crate::edit::*;
+
+    #[test]
+    pub fn parses_sizes() {
+        assert_eq!(parse_size(""), Some(None));
+        assert_eq!(parse_size("4096"), Some(Some(4096)));
+        assert_eq!(parse_size("512K"), Some(Some(512 * 1024)));
+        assert_eq!(parse_size("16 MiB"), Some(Some(16 * 1024 * 1024)));
+        assert_eq!(parse_size("2GB"), Some(Some(2 * 1024 * 1024 * 1024)));
+        assert_eq!(parse_size("twelve"), None);
+    }
+
+    #[test]
+    pub fn edits_partitions() {
+        let mut config = BuildConfig {
+            name: "Test Config".to_owned(),
+            partitions: vec![],
+            image_size: None
+        };
+
+        EditEvent::AddPartition.apply(&mut config);
+        EditEvent::AddPartition.apply(&mut config);
+        EditEvent::SetPreset(0, PresetKind::RedoxFS).apply(&mut config);
+        EditEvent::MovePartition { from: 0, to: 1 }.apply(&mut config);
+
+        assert_eq!(config.partitions.iter().map(|i| i.label.as_str()).collect::<Vec<_>>(), vec!["partition2", "partition1"]);
+        assert_eq!(config.partitions[1].purpose, PartitionPreset::RedoxFS { filesystem: vec![], post_run: None });
+
+        EditEvent::RemovePartition(0).apply(&mut config);
+        assert_eq!(config.partitions.len(), 1);
+    }
+}
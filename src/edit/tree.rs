@@ -0,0 +1,562 @@
+use std::collections::HashSet;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use vizia::icons::*;
+use vizia::prelude::*;
+use vizia::vg;
+
+use crate::AppState;
+use crate::build::*;
+use crate::edit::EditEvent;
+
+const ROW_HEIGHT: f32 = 24.0;
+const INDENT: f32 = 16.0;
+
+/// One row of the file tree. Directories aren't nodes of their own; they're implied by the paths of the nodes below them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeEntry {
+    Directory { path: PathBuf, depth: usize },
+    Node { index: usize, depth: usize }
+}
+
+impl TreeEntry {
+    pub fn depth(&self) -> usize {
+        match self {
+            TreeEntry::Directory { depth, .. } | TreeEntry::Node { depth, .. } => *depth
+        }
+    }
+}
+
+/// Lays out `nodes` as a tree, ordered by path, with a `Directory` entry before the first node inside each directory.
+pub fn tree(nodes: &[FSNode]) -> Vec<TreeEntry> {
+    let mut order = (0..nodes.len()).collect::<Vec<_>>();
+    order.sort_by(|a, b| nodes[*a].path.cmp(&nodes[*b].path));
+
+    let mut seen = HashSet::new();
+    let mut entries = vec![];
+
+    for index in order {
+        let names = nodes[index].path.components()
+            .filter(|i| matches!(i, Component::Normal(_)))
+            .collect::<Vec<_>>();
+
+        let mut dir = PathBuf::from("/");
+        for (depth, name) in names.iter().take(names.len().saturating_sub(1)).enumerate() {
+            dir.push(name);
+            if seen.insert(dir.clone()) {
+                entries.push(TreeEntry::Directory { path: dir.clone(), depth });
+            }
+        }
+
+        seen.insert(nodes[index].path.clone());
+        entries.push(TreeEntry::Node { index, depth: names.len().saturating_sub(1) });
+    }
+
+    entries
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|i| i.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+pub enum FileTreeEvent {
+    Select(Option<usize>)
+}
+
+/// Edits the `FSNode`s of a single partition.
+#[derive(Lens)]
+pub struct FileTree {
+    partition: usize,
+    selected: Option<usize>
+}
+
+impl View for FileTree {
+    fn element(&self) -> Option<&'static str> {
+        Some("file-tree")
+    }
+
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|e, _| match e {
+            FileTreeEvent::Select(selected) => self.selected = *selected
+        });
+
+        event.map(|e, _| if let EditEvent::RemoveNode { partition, node } = e {
+            self.node_removed(*partition, *node);
+        });
+    }
+}
+
+impl FileTree {
+    /// Keeps the selection on the same node once `node` is removed from `partition`. Nodes are selected by index, so
+    /// removing an earlier one would otherwise move the selection, and the open form, onto its neighbour.
+    fn node_removed(&mut self, partition: usize, node: usize) {
+        if partition != self.partition {
+            return;
+        }
+
+        self.selected = match self.selected {
+            Some(selected) if selected == node => None,
+            Some(selected) if selected > node => Some(selected - 1),
+            selected => selected
+        };
+    }
+
+    pub fn new(cx: &mut Context, partition: usize) -> Handle<Self> {
+        View::build(Self { partition, selected: None }, cx, move |cx| {
+            ScrollView::new(cx, 0.0, 0.0, false, true, move |cx| {
+                let nodes = AppState::config.map(move |i| i.as_ref()
+                    .and_then(|i| i.partitions.get(partition))
+                    .map(|i| i.purpose.filesystem().to_vec())
+                    .unwrap_or_default());
+
+                Binding::new(cx, nodes, move |cx, nodes| {
+                    let nodes = nodes.get(cx);
+                    let entries = tree(&nodes);
+                    let rows = entries.iter()
+                        .enumerate()
+                        .filter_map(|(row, i)| match i {
+                            TreeEntry::Node { index, .. } => Some((*index, row)),
+                            _ => None
+                        })
+                        .collect::<Vec<_>>();
+
+                    let links = nodes.iter()
+                        .enumerate()
+                        .flat_map(|(index, node)| node.dependencies.iter()
+                            .flatten()
+                            .filter_map(|dep| nodes.iter().position(|i| i.path == *dep))
+                            .map(move |dep| (index, dep)))
+                        .filter_map(|(from, to)| Some((rows.iter().find(|i| i.0 == from)?.1, rows.iter().find(|i| i.0 == to)?.1)))
+                        .collect::<Vec<_>>();
+
+                    HStack::new(cx, |cx| {
+                        DependencyLinks::new(cx, links)
+                            .height(Pixels(entries.len() as f32 * ROW_HEIGHT));
+
+                        VStack::new(cx, |cx| {
+                            for entry in entries {
+                                let row = HStack::new(cx, |cx| {
+                                    match &entry {
+                                        TreeEntry::Directory { path, .. } => {
+                                            Label::new(cx, ICON_FOLDER);
+                                            Label::new(cx, file_name(path));
+                                        },
+                                        TreeEntry::Node { index, .. } => {
+                                            Label::new(cx, ICON_FILE);
+                                            Label::new(cx, file_name(&nodes[*index].path));
+                                            Element::new(cx).width(Stretch(1.0));
+                                            Label::new(cx, nodes[*index].source.kind().name())
+                                                .class("node-source");
+                                        }
+                                    }
+                                })
+                                .class("tree-row")
+                                .height(Pixels(ROW_HEIGHT))
+                                .child_left(Pixels(entry.depth() as f32 * INDENT));
+
+                                if let TreeEntry::Node { index, .. } = entry {
+                                    row.toggle_class("selected", FileTree::selected.map(move |i| *i == Some(index)))
+                                        .on_press(move |cx| cx.emit(FileTreeEvent::Select(Some(index))));
+                                }
+                            }
+                        })
+                        .height(Auto);
+                    })
+                    .height(Auto);
+                });
+            })
+            .class("field");
+
+            HStack::new(cx, move |cx| {
+                Button::new(cx, move |cx| cx.emit(EditEvent::AddNode { partition, path: PathBuf::from("/new-file") }), |cx| {
+                    HStack::new(cx, |cx| {
+                        Label::new(cx, ICON_PLUS);
+                        Label::new(cx, "Add File");
+                    })
+                })
+                .class("secondary");
+            })
+            .height(Auto);
+
+            Binding::new(cx, FileTree::selected, move |cx, selected| {
+                let Some(node) = selected.get(cx) else { return; };
+
+                NodeForm::new(cx, partition, node);
+            });
+        })
+    }
+}
+
+fn node_lens(partition: usize, index: usize) -> impl Lens<Target=Option<FSNode>> {
+    AppState::config.map(move |i| i.as_ref()
+        .and_then(|i| i.partitions.get(partition))
+        .and_then(|i| i.purpose.filesystem().get(index))
+        .cloned())
+}
+
+fn source_text(partition: usize, index: usize, get: fn(&NodeSource) -> String) -> impl Lens<Target=String> {
+    node_lens(partition, index).map(move |i| i.as_ref().map(|i| get(&i.source)).unwrap_or_default())
+}
+
+/// Edits the path, source and dependencies of the selected node.
+pub struct NodeForm;
+
+impl View for NodeForm {
+    fn element(&self) -> Option<&'static str> {
+        Some("node-form")
+    }
+}
+
+impl NodeForm {
+    pub fn new(cx: &mut Context, partition: usize, index: usize) -> Handle<Self> {
+        View::build(Self, cx, move |cx| {
+            Binding::new(cx, node_lens(partition, index), move |cx, node| {
+                let Some(node) = node.get(cx) else { return; };
+
+                let parent = node.path.parent().map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from("/"));
+                let name = file_name(&node.path);
+
+                HStack::new(cx, |cx| {
+                    Label::new(cx, "Name");
+                    Textbox::new(cx, node_lens(partition, index).map(|i| i.as_ref().map(|i| file_name(&i.path)).unwrap_or_default()))
+                        .on_submit(move |cx, text, _| cx.emit(EditEvent::SetNodePath { partition, node: index, path: parent.join(text) }))
+                        .width(Stretch(1.0));
+                })
+                .class("editor-field");
+
+                HStack::new(cx, |cx| {
+                    Label::new(cx, "Directory");
+                    Textbox::new(cx, node_lens(partition, index).map(|i| i.as_ref().and_then(|i| i.path.parent()).map(|i| i.display().to_string()).unwrap_or_default()))
+                        .on_submit(move |cx, text, _| cx.emit(EditEvent::SetNodePath { partition, node: index, path: Path::new("/").join(text).join(&name) }))
+                        .width(Stretch(1.0));
+                })
+                .class("editor-field");
+
+                let kind = node.source.kind();
+                let source = node.source.clone();
+                HStack::new(cx, |cx| {
+                    Label::new(cx, "Source");
+                    Dropdown::new(cx, move |cx| {
+                        Label::new(cx, kind.name());
+                    }, move |cx| {
+                        for kind in SourceKind::ALL {
+                            let source = source.with_kind(kind);
+                            Label::new(cx, kind.name())
+                                .on_press(move |cx| {
+                                    cx.emit(EditEvent::SetNodeSource { partition, node: index, source: source.clone() });
+                                    cx.emit(PopupEvent::Close);
+                                });
+                        }
+                    })
+                    .width(Stretch(1.0));
+                })
+                .class("editor-field");
+
+                source_form(cx, partition, index, &node.source);
+
+                let own = node.path.clone();
+                VStack::new(cx, |cx| {
+                    Label::new(cx, "Dependencies");
+
+                    for dependency in node.dependencies.iter().flatten().cloned() {
+                        HStack::new(cx, |cx| {
+                            Label::new(cx, ICON_LINK);
+                            Label::new(cx, dependency.display().to_string())
+                                .width(Stretch(1.0));
+                            Button::new(cx, move |cx| cx.emit(EditEvent::RemoveDependency { partition, node: index, dependency: dependency.clone() }), |cx| Label::new(cx, ICON_X));
+                        })
+                        .class("editor-row");
+                    }
+
+                    Dropdown::new(cx, |cx| {
+                        HStack::new(cx, |cx| {
+                            Label::new(cx, ICON_PLUS);
+                            Label::new(cx, "Add Dependency");
+                        });
+                    }, move |cx| {
+                        // Dependencies may be provided by nodes in any partition.
+                        let paths = AppState::config.get(cx)
+                            .map(|i| i.partitions.iter()
+                                .flat_map(|i| i.purpose.filesystem())
+                                .map(|i| i.path.clone())
+                                .filter(|i| *i != own)
+                                .collect::<Vec<_>>())
+                            .unwrap_or_default();
+
+                        for path in paths {
+                            Label::new(cx, path.display().to_string())
+                                .on_press(move |cx| {
+                                    cx.emit(EditEvent::AddDependency { partition, node: index, dependency: path.clone() });
+                                    cx.emit(PopupEvent::Close);
+                                });
+                        }
+                    });
+                })
+                .height(Auto);
+
+                Button::new(cx, move |cx| cx.emit(EditEvent::RemoveNode { partition, node: index }), |cx| {
+                    HStack::new(cx, |cx| {
+                        Label::new(cx, ICON_TRASH);
+                        Label::new(cx, "Remove File");
+                    })
+                })
+                .class("danger");
+            });
+        })
+    }
+}
+
+/// The fields specific to each kind of `NodeSource`.
+fn source_form(cx: &mut Context, partition: usize, index: usize, source: &NodeSource) {
+    let set = move |cx: &mut EventContext, source: NodeSource| cx.emit(EditEvent::SetNodeSource { partition, node: index, source });
+
+    match source.clone() {
+        NodeSource::Static { location, sha256 } | NodeSource::Prebuilt { location, sha256 } => {
+            let kind = source.kind();
+            let artifact = move |location: String, sha256: Option<String>| NodeSource::Static { location, sha256 }.with_kind(kind);
+
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Location");
+                Textbox::new(cx, source_text(partition, index, |i| match i {
+                    NodeSource::Static { location, .. } | NodeSource::Prebuilt { location, .. } => location.clone(),
+                    _ => String::new()
+                }))
+                    .on_submit(move |cx, text, _| set(cx, artifact(text, sha256.clone())))
+                    .width(Stretch(1.0));
+            })
+            .class("editor-field");
+
+            HStack::new(cx, |cx| {
+                Label::new(cx, "SHA-256");
+                Textbox::new(cx, source_text(partition, index, |i| match i {
+                    NodeSource::Static { sha256, .. } | NodeSource::Prebuilt { sha256, .. } => sha256.clone().unwrap_or_default(),
+                    _ => String::new()
+                }))
+                    .on_submit(move |cx, text, _| {
+                        let sha256 = Some(text.trim().to_owned()).filter(|i| !i.is_empty());
+                        set(cx, artifact(location.clone(), sha256));
+                    })
+                    .width(Stretch(1.0));
+            })
+            .class("editor-field");
+        },
+        NodeSource::Cargo { repo, origin, target, features } => {
+            // Every field is submitted on its own, so the others are carried over from the source as it was
+            let cargo = move |repo: String, origin: Option<GitOrigin>, target: Option<String>, features: Option<Vec<String>>| NodeSource::Cargo { repo, origin, target, features };
+
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Repository");
+                let (origin, target, features) = (origin.clone(), target.clone(), features.clone());
+                Textbox::new(cx, source_text(partition, index, |i| match i {
+                    NodeSource::Cargo { repo, .. } => repo.clone(),
+                    _ => String::new()
+                }))
+                    .on_submit(move |cx, text, _| set(cx, cargo(text, origin.clone(), target.clone(), features.clone())))
+                    .width(Stretch(1.0));
+            })
+            .class("editor-field");
+
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Revision");
+
+                let revision = match &origin {
+                    None | Some(GitOrigin::Head) => String::new(),
+                    Some(GitOrigin::Branch(name)) | Some(GitOrigin::Commit(name)) => name.clone()
+                };
+                let current = match &origin {
+                    None | Some(GitOrigin::Head) => "HEAD",
+                    Some(GitOrigin::Branch(_)) => "Branch",
+                    Some(GitOrigin::Commit(_)) => "Commit"
+                };
+
+                let (choices, choice_target, choice_features) = (repo.clone(), target.clone(), features.clone());
+                Dropdown::new(cx, move |cx| {
+                    Label::new(cx, current);
+                }, move |cx| {
+                    let origins: [(&str, fn(String) -> GitOrigin); 3] = [
+                        ("HEAD", |_| GitOrigin::Head),
+                        ("Branch", GitOrigin::Branch),
+                        ("Commit", GitOrigin::Commit)
+                    ];
+
+                    for (name, origin) in origins {
+                        let (repo, revision) = (choices.clone(), revision.clone());
+                        let (target, features) = (choice_target.clone(), choice_features.clone());
+                        Label::new(cx, name)
+                            .on_press(move |cx| {
+                                set(cx, cargo(repo.clone(), Some(origin(revision.clone())), target.clone(), features.clone()));
+                                cx.emit(PopupEvent::Close);
+                            });
+                    }
+                });
+
+                let (repo, origin, target, features) = (repo.clone(), origin.clone(), target.clone(), features.clone());
+                Textbox::new(cx, source_text(partition, index, |i| match i {
+                    NodeSource::Cargo { origin: Some(GitOrigin::Branch(name) | GitOrigin::Commit(name)), .. } => name.clone(),
+                    _ => String::new()
+                }))
+                    .on_submit(move |cx, text, _| {
+                        let origin = match &origin {
+                            Some(GitOrigin::Commit(_)) => GitOrigin::Commit(text),
+                            _ if text.is_empty() => GitOrigin::Head,
+                            _ => GitOrigin::Branch(text)
+                        };
+
+                        set(cx, cargo(repo.clone(), Some(origin), target.clone(), features.clone()));
+                    })
+                    .width(Stretch(1.0));
+            })
+            .class("editor-field");
+
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Target");
+                let (repo, origin, features) = (repo.clone(), origin.clone(), features.clone());
+                Textbox::new(cx, source_text(partition, index, |i| match i {
+                    NodeSource::Cargo { target, .. } => target.clone().unwrap_or_default(),
+                    _ => String::new()
+                }))
+                    .on_submit(move |cx, text, _| {
+                        let target = Some(text.trim().to_owned()).filter(|i| !i.is_empty());
+                        set(cx, cargo(repo.clone(), origin.clone(), target, features.clone()));
+                    })
+                    .width(Stretch(1.0));
+            })
+            .class("editor-field");
+
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Features");
+                Textbox::new(cx, source_text(partition, index, |i| match i {
+                    NodeSource::Cargo { features, .. } => features.as_ref().map(|i| i.join(" ")).unwrap_or_default(),
+                    _ => String::new()
+                }))
+                    .on_submit(move |cx, text, _| {
+                        let features = text.split([' ', ',']).filter(|i| !i.is_empty()).map(str::to_owned).collect::<Vec<_>>();
+                        set(cx, cargo(repo.clone(), origin.clone(), target.clone(), Some(features).filter(|i| !i.is_empty())));
+                    })
+                    .width(Stretch(1.0));
+            })
+            .class("editor-field");
+        },
+        NodeSource::Custom(command) => {
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Command");
+                let command = command.clone();
+                Textbox::new(cx, source_text(partition, index, |i| match i {
+                    NodeSource::Custom(command) => command.command_line(),
+                    _ => String::new()
+                }))
+                    .on_submit(move |cx, text, _| if let Some(cmd) = Command::parse_command_line(&text) {
+                        set(cx, NodeSource::Custom(Command { cmd, ..command.clone() }));
+                    })
+                    .width(Stretch(1.0));
+            })
+            .class("editor-field");
+
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Directory");
+                Textbox::new(cx, source_text(partition, index, |i| match i {
+                    NodeSource::Custom(Command { cwd: Some(cwd), .. }) => cwd.display().to_string(),
+                    _ => String::new()
+                }))
+                    .on_submit(move |cx, text, _| set(cx, NodeSource::Custom(Command {
+                        cwd: Some(PathBuf::from(text)).filter(|i| !i.as_os_str().is_empty()),
+                        ..command.clone()
+                    })))
+                    .width(Stretch(1.0));
+            })
+            .class("editor-field");
+        }
+    }
+}
+
+/// Draws an arc in the gutter for every dependency, connecting the rows of the two nodes.
+pub struct DependencyLinks {
+    links: Vec<(usize, usize)>
+}
+
+impl View for DependencyLinks {
+    fn element(&self) -> Option<&'static str> {
+        Some("dependency-links")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        let paint = vg::Paint::color(vg::Color::rgba(65, 117, 171, 200))
+            .with_line_width(1.5);
+
+        for (from, to) in &self.links {
+            let y1 = bounds.y + (*from as f32 + 0.5) * ROW_HEIGHT;
+            let y2 = bounds.y + (*to as f32 + 0.5) * ROW_HEIGHT;
+            let right = bounds.x + bounds.w;
+            let reach = (bounds.w * (from.abs_diff(*to) as f32 / 8.0).min(1.0)).max(4.0);
+
+            let mut path = vg::Path::new();
+            path.move_to(right, y1);
+            path.bezier_to(right - reach, y1, right - reach, y2, right, y2);
+            canvas.stroke_path(&mut path, &paint);
+
+            let mut arrow = vg::Path::new();
+            arrow.move_to(right - 4.0, y2 - 3.0);
+            arrow.line_to(right, y2);
+            arrow.line_to(right - 4.0, y2 + 3.0);
+            canvas.stroke_path(&mut arrow, &paint);
+        }
+    }
+}
+
+impl DependencyLinks {
+    pub fn new(cx: &mut Context, links: Vec<(usize, usize)>) -> Handle<Self> {
+        View::build(Self { links }, cx, |_| {})
+            .width(Pixels(INDENT * 2.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::build::*;
+    use crate::edit::tree::*;
+
+    #[test]
+    pub fn groups_nodes_by_directory() {
+        let nodes = ["/usr/bin/ion", "/bin/sh", "/usr/bin/cat", "/etc/hostname"]
+            .into_iter()
+            .map(|path| FSNode {
+                path: PathBuf::from(path),
+                source: NodeSource::Static { location: path.to_owned(), sha256: None },
+                dependencies: None
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(tree(&nodes), vec![
+            TreeEntry::Directory { path: PathBuf::from("/bin"), depth: 0 },
+            TreeEntry::Node { index: 1, depth: 1 },
+            TreeEntry::Directory { path: PathBuf::from("/etc"), depth: 0 },
+            TreeEntry::Node { index: 3, depth: 1 },
+            TreeEntry::Directory { path: PathBuf::from("/usr"), depth: 0 },
+            TreeEntry::Directory { path: PathBuf::from("/usr/bin"), depth: 1 },
+            TreeEntry::Node { index: 2, depth: 2 },
+            TreeEntry::Node { index: 0, depth: 2 },
+        ]);
+    }
+
+    #[test]
+    pub fn follows_selected_node() {
+        let removed = |selected, partition, node| {
+            let mut tree = FileTree { partition: 0, selected };
+            tree.node_removed(partition, node);
+            tree.selected
+        };
+
+        assert_eq!(removed(Some(2), 0, 0), Some(1));
+        assert_eq!(removed(Some(2), 0, 2), None);
+        assert_eq!(removed(Some(2), 0, 3), Some(2));
+        assert_eq!(removed(Some(2), 1, 0), Some(2));
+        assert_eq!(removed(None, 0, 0), None);
+    }
+}
@@ -1,15 +1,22 @@
 use vizia::prelude::*;
 
 macro_rules! multi_error {
-    ($name:ident($($manual:ident),*); $($err:ident = $obj:ty);*) => {
+    ($name:ident($($manual:ident = $message:literal),*); $($err:ident = $obj:ty);*) => {
         #[derive(Debug)]
         pub enum $name {
             $($err($obj),)*
             $($manual),*
         }
         
-        impl std::fmt::Display for Error { fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { std::fmt::Debug::fmt(self, f) } }
-        impl std::error::Error for Error {}
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    $($name::$err(err) => std::fmt::Display::fmt(err, f),)*
+                    $($name::$manual => f.write_str($message)),*
+                }
+            }
+        }
+        impl std::error::Error for $name {}
     
         $(impl From<$obj> for $name { fn from(value: $obj) -> Self { Self::$err(value) } })*
         // $(impl From<Arc<$obj>> for $name { fn from(value: $obj) -> Arc<Self> { Arc::new(Self::$err(value)) } })*
@@ -17,8 +24,18 @@ macro_rules! multi_error {
     }
 }
 
-multi_error! { Error();
+multi_error! { Error(Cancelled = "the build was cancelled");
     IoError = std::io::Error;
     SpannedError = ron::error::SpannedError;
-    ProxyEmitError = ProxyEmitError
+    RonError = ron::Error;
+    JsonError = serde_json::Error;
+    Json5Error = json5::Error;
+    TomlError = toml::de::Error;
+    TomlSerError = toml::ser::Error;
+    ProxyEmitError = ProxyEmitError;
+    ScheduleError = crate::build::ScheduleError;
+    LayoutError = crate::build::LayoutError;
+    CommandError = crate::build::CommandError;
+    ChecksumError = crate::build::ChecksumError;
+    InvalidConfig = crate::build::Diagnostics
 }
\ No newline at end of file
@@ -1,10 +1,12 @@
-use std::fs::File;
-use std::io::Read;
 use std::path::PathBuf;
+use std::process::ExitCode;
 use std::sync::Arc;
+use std::sync::Mutex;
+use clap::Parser;
 use native_dialog::MessageDialog;
 use vizia::prelude::*;
 
+mod cli;
 mod components;
 mod error;
 mod build;
@@ -17,21 +19,100 @@ use crate::build::Builder;
 struct AppState {
     config: Option<build::BuildConfig>,
     started: Option<Instant>,
+    jobs: Vec<build::Job>,
+    /// The job whose log and error are shown, by id.
+    selected: Option<usize>,
+    build: Option<build::BuildHandle>,
+    cache_dir: PathBuf,
+    cache_limit: Option<u64>,
+    path: Option<PathBuf>,
+    format: build::ConfigFormat,
+    dirty: bool,
+    editing: bool,
 }
 
 #[derive(Clone)]
 pub enum AppEvent {
-    LoadConfig(build::BuildConfig),
+    LoadConfig(build::BuildConfig, PathBuf, build::ConfigFormat),
+    Save,
+    SaveAs,
+    SaveTo(PathBuf, build::ConfigFormat),
+    Discard,
+    Edit(bool),
     Error(Arc<Error>),
     BuildStarted(bool),
+    Build(build::BuildEvent),
+    SelectJob(Option<usize>),
+    SetCacheDir(PathBuf),
+    SetCacheLimit(Option<u64>),
+    ClearCache,
     Refresh
 }
 
 impl Model for AppState {
     fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event: &WindowEvent, meta| match window_event {
+            WindowEvent::WindowClose if self.dirty => {
+                meta.consume();
+                cx.spawn(|cx| {
+                    let discard = MessageDialog::new()
+                        .set_title("Unsaved Changes")
+                        .set_type(native_dialog::MessageType::Warning)
+                        .set_text("The configuration has unsaved changes. Close without saving?")
+                        .show_confirm()
+                        .unwrap_or(false);
+                    
+                    if discard {
+                        cx.emit(AppEvent::Discard).unwrap_or_default();
+                    }
+                });
+            },
+            _ => {}
+        });
+        
+        event.map(|edit: &edit::EditEvent, _| if let Some(config) = &mut self.config {
+            edit.apply(config);
+            self.dirty = true;
+        });
+        
         match event.take() {
             None => return,
-            Some(AppEvent::LoadConfig(config)) => self.config = Some(config.clone()),
+            Some(AppEvent::LoadConfig(config, path, format)) => {
+                self.config = Some(config);
+                self.path = Some(path);
+                self.format = format;
+                self.dirty = false;
+            },
+            Some(AppEvent::Save) => match &self.path {
+                Some(path) => cx.emit(AppEvent::SaveTo(path.clone(), self.format)),
+                None => cx.emit(AppEvent::SaveAs)
+            },
+            Some(AppEvent::SaveAs) => {
+                let format = self.format;
+                cx.spawn(move |cx| {
+                    if let Err(err) = save_file(cx, format) {
+                        cx.emit(AppEvent::Error(Arc::new(err)))
+                            .expect("Failed to propagate error");
+                    }
+                });
+            },
+            Some(AppEvent::SaveTo(path, format)) => {
+                let Some(config) = &self.config else { return; };
+                
+                match build::save(&path, config, format) {
+                    Ok(()) => {
+                        self.path = Some(path);
+                        self.format = format;
+                        self.dirty = false;
+                    },
+                    Err(err) => cx.emit(AppEvent::Error(Arc::new(err)))
+                }
+            },
+            Some(AppEvent::Edit(editing)) => self.editing = editing,
+            Some(AppEvent::Discard) => {
+                self.dirty = false;
+                cx.emit(WindowEvent::WindowClose);
+            },
             Some(AppEvent::Error(err)) => {
                 let err = Arc::clone(&err);
                 std::thread::spawn(move || MessageDialog::new()
@@ -41,12 +122,91 @@ impl Model for AppState {
                     .show_alert()
                     .unwrap());
             },
-            Some(AppEvent::BuildStarted(started)) => self.started = if started { Some(Instant::now()) } else { None },
+            Some(AppEvent::BuildStarted(true)) => {
+                let Some(config) = self.config.clone() else { return; };
+                
+                if self.build.is_some() {
+                    return;
+                }
+                
+                let handle = build::BuildHandle::default();
+                let mut options = build::BuildOptions::for_config(&config);
+                options.cache_dir = self.cache_dir.clone();
+                options.cache_limit = self.cache_limit;
+                
+                self.build = Some(handle.clone());
+                self.started = Some(Instant::now());
+                self.editing = false;
+                
+                cx.spawn(move |cx| {
+                    let cx = Mutex::new(cx);
+                    build::build(config, options, handle, |event| cx.lock()
+                        .unwrap()
+                        .emit(AppEvent::Build(event))
+                        .unwrap_or_default()
+                    ).ok();
+                });
+            },
+            Some(AppEvent::BuildStarted(false)) => if let Some(handle) = &self.build {
+                handle.cancel();
+            },
+            Some(AppEvent::Build(event)) => self.on_build_event(cx, event),
+            Some(AppEvent::SelectJob(id)) => self.selected = id,
+            Some(AppEvent::SetCacheDir(cache_dir)) => self.cache_dir = cache_dir,
+            Some(AppEvent::SetCacheLimit(cache_limit)) => self.cache_limit = cache_limit,
+            // Clearing the cache under a running build would pull its checkouts out from under it
+            Some(AppEvent::ClearCache) => if self.build.is_none() {
+                if let Err(err) = build::clear_cache(&self.cache_dir) {
+                    cx.emit(AppEvent::Error(Arc::new(Error::from(err))));
+                }
+            },
             Some(AppEvent::Refresh) => {}
         }
     }
 }
 
+impl AppState {
+    fn on_build_event(&mut self, cx: &mut EventContext, event: build::BuildEvent) {
+        match event {
+            build::BuildEvent::Queued(jobs) => {
+                self.jobs = jobs;
+                self.selected = None;
+            },
+            build::BuildEvent::JobStarted { id, at } => if let Some(job) = self.jobs.get_mut(id) {
+                job.status = build::JobStatus::Running;
+                job.started = Some(at);
+            },
+            build::BuildEvent::JobOutput { id, line } => if let Some(job) = self.jobs.get_mut(id) {
+                job.log.push(line);
+            },
+            build::BuildEvent::JobFinished { id, at, result } => if let Some(job) = self.jobs.get_mut(id) {
+                job.finished = Some(at);
+                match result {
+                    Ok(status) => job.status = status,
+                    Err(err) if matches!(*err, Error::Cancelled) => job.status = build::JobStatus::Cancelled,
+                    Err(err) => {
+                        job.status = build::JobStatus::Failed;
+                        job.error = Some(err);
+                    }
+                }
+            },
+            build::BuildEvent::Finished(result) => {
+                for job in self.jobs.iter_mut().filter(|i| i.status == build::JobStatus::Pending) {
+                    job.status = build::JobStatus::Cancelled;
+                }
+                
+                self.build = None;
+                self.started = None;
+                
+                match result {
+                    Err(err) if !matches!(*err, Error::Cancelled) => cx.emit(AppEvent::Error(err)),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
 struct FallbackScreen {}
 impl View for FallbackScreen {}
 
@@ -54,43 +214,71 @@ fn load_file(cx: &mut ContextProxy) -> Result<(), Error> {
     if let Ok(res) = native_dialog::FileDialog::new()
         .set_title("Open Configuration")
         .set_location("~/")
-        .add_filter("Redox OS Build configuration", &["ron", "json", "json5", "toml", "cson"])
+        .add_filter("Redox OS Build configuration", &["ron", "json", "json5", "toml"])
         .show_open_single_file() {
             
         let Some(path) = res else { return Ok(()); };
         
-        let mut file = match File::open(path) {
-            Ok(file) => file,
-            Err(err) => {
-                cx.emit(AppEvent::Error(Arc::new(Error::from(err))))?;
-                return Ok(());
-            }
-        };
-        
-        let mut buf = String::new();
-        if let Err(err) = file.read_to_string(&mut buf) {
-            cx.emit(AppEvent::Error(Arc::new(Error::from(err))))?;
-        }
-        
-        let config = match ron::from_str(&buf) {
+        let (config, format) = match build::load(&path) {
             Ok(config) => config,
             Err(err) => { 
-                cx.emit(AppEvent::Error(Arc::new(Error::from(err))))?;
+                cx.emit(AppEvent::Error(Arc::new(err)))?;
                 return Ok(())
             }
         };
         
-        cx.emit(AppEvent::LoadConfig(config))?;
+        cx.emit(AppEvent::LoadConfig(config, path, format))?;
     };
     
     Ok(())    
 }
 
-fn main() {
+/// Asks where to save the configuration. The format follows the chosen file's extension, falling back to `format`.
+fn save_file(cx: &mut ContextProxy, format: build::ConfigFormat) -> Result<(), Error> {
+    let formats = std::iter::once(format)
+        .chain(build::ConfigFormat::ALL.into_iter().filter(|i| *i != format))
+        .collect::<Vec<_>>();
+    
+    let dialog = formats.iter()
+        .fold(native_dialog::FileDialog::new()
+            .set_title("Save Configuration")
+            .set_location("~/"), |dialog, format| dialog.add_filter(format.name(), format.extensions()));
+    
+    if let Ok(res) = dialog.show_save_single_file() {
+        let Some(mut path) = res else { return Ok(()); };
+        
+        let format = match build::ConfigFormat::from_path(&path) {
+            Some(format) => format,
+            None => {
+                path.set_extension(format.extensions()[0]);
+                format
+            }
+        };
+        
+        cx.emit(AppEvent::SaveTo(path, format))?;
+    };
+    
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    if let Some(action) = cli::Cli::parse().action {
+        return cli::run(action);
+    }
+    
     Application::new(|cx| {
         AppState { 
             config: None, 
-            started: None
+            started: None,
+            jobs: vec![],
+            selected: None,
+            build: None,
+            cache_dir: PathBuf::from(build::CACHE_DIR),
+            cache_limit: None,
+            path: None,
+            format: build::ConfigFormat::Ron,
+            dirty: false,
+            editing: false
         }.build(cx);
         
         cx.spawn(|cx| loop {
@@ -101,9 +289,9 @@ fn main() {
         cx.add_stylesheet(PathBuf::from("./theme.css"))
             .expect("Failed to load stylesheet");
             
-        Binding::new(cx, AppState::config, |cx, item| {
-            if let Some(config) = item.get(cx) {
-                Builder::new(cx, config);
+        Binding::new(cx, AppState::config.map(Option::is_some), |cx, loaded| {
+            if loaded.get(cx) {
+                Builder::new(cx);
             } else {
                 HStack::new(cx, |cx| {
                     Button::new(cx, |event| event.spawn(|cx| {
@@ -120,4 +308,6 @@ fn main() {
     .inner_size((480, 320))
     .min_inner_size(Some((220, 160)))
     .run();
+    
+    ExitCode::SUCCESS
 }